@@ -1,18 +1,32 @@
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
 use crate::{
     asset::Asset,
-    formats::{FrmExporter, FrmImporter, GltfExporter, GltfImporter, P3mExporter, P3mImporter},
+    formats::gltf::rename_buffer_file,
+    formats::{
+        FrmExporter, FrmImporter, FrmVersion, GltfCompression, GltfExporter, GltfFormat,
+        GltfImporter, GltfInterpolation, KeyframeReduction, P3mExporter, P3mImporter, RootMotion,
+    },
+    profile::SkeletonProfile,
     scene::Scene,
+    skinning::SkinningOptions,
 };
 
+/// The name of the directory that contains the skeleton profiles, next to the executable.
+const PROFILES_DIR: &str = "profiles";
+
 /// Defines a type that can import asset files into a scene.
 #[allow(unused_variables)]
 pub trait Importer {
-    /// Imports an asset file into a scene.
-    fn import(&self, asset: &Asset, scene: &mut Scene) -> Result<()>;
+    /// Imports an asset file into a scene. Returns warnings about the data that couldn't be
+    /// imported as is, which don't stop the import.
+    fn import(&self, asset: &Asset, scene: &mut Scene) -> Result<Vec<String>>;
     /// Returns the file extensions supported by the importer. These extensions are used to
     /// select the appropriate importer given an asset file.
     ///
@@ -23,8 +37,21 @@ pub trait Importer {
 /// Defines a type that can export a scene into asset files.
 #[allow(unused_variables)]
 pub trait Exporter {
-    /// Exports a scene into one or more asset files.
-    fn export(&self, scene: &Scene) -> Result<Vec<Asset>>;
+    /// Exports a scene into one or more asset files. Also returns warnings about the data that
+    /// couldn't be exported as is, which don't stop the export.
+    fn export(&self, scene: &Scene) -> Result<(Vec<Asset>, Vec<String>)>;
+}
+
+/// Specifies what happens when several exported files have the same name.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NameCollision {
+    /// A numeric suffix is appended to the name of the file (e.g., "mesh_1.p3m").
+    #[default]
+    Suffix,
+    /// The file replaces the one exported before it.
+    Overwrite,
+    /// The export stops with an error.
+    Fail,
 }
 
 /// The converter for certain asset format.
@@ -32,12 +59,18 @@ pub trait Exporter {
 pub struct Converter {
     /// The display name of the output asset format.
     pub name: &'static str,
+    /// Specifies what happens when several exported files have the same name.
+    pub name_collision: NameCollision,
+    /// Specifies how the root translations of FRM animations are converted.
+    pub root_motion: RootMotion,
+    /// The skeleton profiles used to map the joints of imported glTF files.
+    profiles: Vec<SkeletonProfile>,
     exporters: Vec<Box<dyn Exporter>>,
 }
 
 impl Converter {
     pub fn convert(&self, files: &[String], out_path: &str) {
-        let importers = importers();
+        let importers = importers(self.root_motion, &self.profiles);
         let importers: HashMap<_, _> = importers
             .iter()
             .flat_map(|importer| importer.extensions().iter().map(move |ext| (ext, importer)))
@@ -63,8 +96,9 @@ impl Converter {
 
                         eprint!("Importing \"{}.{}\"... ", asset.name(), asset.extension());
                         match importer.import(&asset, &mut scene) {
-                            Ok(_) => {
+                            Ok(warnings) => {
                                 eprintln!("Success!",);
+                                print_warnings(&warnings);
                                 Some(scene)
                             }
                             Err(err) => {
@@ -91,12 +125,27 @@ impl Converter {
                     eprintln!("Failed to create the output directory: {}", err)
                 });
 
+                let mut exported_paths = HashSet::new();
                 for exporter in &self.exporters {
                     // Export assets.
                     match exporter.export(&scene) {
-                        Ok(assets) => {
-                            for asset in assets {
-                                let path = PathBuf::from(out_path).join(asset.path());
+                        Ok((mut assets, warnings)) => {
+                            print_warnings(&warnings);
+                            let paths = match resolve_paths(
+                                &mut assets,
+                                Path::new(out_path),
+                                &exported_paths,
+                                self.name_collision,
+                            ) {
+                                Ok(paths) => paths,
+                                Err(err) => {
+                                    eprintln!("Failed to export the scene: {:#}", err);
+                                    return;
+                                }
+                            };
+                            exported_paths.extend(paths.iter().cloned());
+
+                            for (asset, path) in assets.iter().zip(paths) {
                                 match fs::write(&path, &asset.bytes) {
                                     Ok(_) => {
                                         eprintln!(
@@ -121,7 +170,7 @@ impl Converter {
                             }
                         }
                         Err(err) => {
-                            eprintln!("Failed to export the scene: {}", err);
+                            eprintln!("Failed to export the scene: {:#}", err);
                         }
                     }
                 }
@@ -134,24 +183,410 @@ impl Converter {
 }
 
 // Returns all importers available.
-fn importers() -> Vec<Box<dyn Importer>> {
+fn importers(root_motion: RootMotion, profiles: &[SkeletonProfile]) -> Vec<Box<dyn Importer>> {
     vec![
-        Box::<FrmImporter>::default(),
+        Box::new(FrmImporter { root_motion }),
         Box::<P3mImporter>::default(),
-        Box::<GltfImporter>::default(),
+        Box::new(GltfImporter {
+            profiles: profiles.to_vec(),
+            skinning: SkinningOptions {
+                split_triangles: true,
+                ..Default::default()
+            },
+            merge_primitives: true,
+            ..Default::default()
+        }),
     ]
 }
 
-/// Returns all converters available.
-pub fn converters() -> Vec<Converter> {
+/// Returns all converters available. FRM animations are converted with the given root motion
+/// mode, and glTF joints are named with the given skeleton profiles.
+pub fn converters(root_motion: RootMotion, profiles: &[SkeletonProfile]) -> Vec<Converter> {
     vec![
         Converter {
             name: ".GLB (glTF)",
-            exporters: vec![Box::<GltfExporter>::default()],
+            name_collision: NameCollision::default(),
+            root_motion,
+            profiles: profiles.to_vec(),
+            exporters: vec![Box::new(GltfExporter {
+                profiles: profiles.to_vec(),
+                ..Default::default()
+            })],
+        },
+        Converter {
+            name: ".glTF + .bin (glTF, separate files)",
+            name_collision: NameCollision::default(),
+            root_motion,
+            profiles: profiles.to_vec(),
+            exporters: vec![Box::new(GltfExporter {
+                format: GltfFormat::Separate,
+                profiles: profiles.to_vec(),
+                ..Default::default()
+            })],
+        },
+        Converter {
+            name: ".GLB (glTF, smooth animations)",
+            name_collision: NameCollision::default(),
+            root_motion,
+            profiles: profiles.to_vec(),
+            exporters: vec![Box::new(GltfExporter {
+                profiles: profiles.to_vec(),
+                interpolation: GltfInterpolation::CubicSpline,
+                ..Default::default()
+            })],
+        },
+        Converter {
+            name: ".GLB (glTF, quantized and compressed)",
+            name_collision: NameCollision::default(),
+            root_motion,
+            profiles: profiles.to_vec(),
+            exporters: vec![Box::new(GltfExporter {
+                profiles: profiles.to_vec(),
+                quantize: true,
+                compression: GltfCompression::Meshopt,
+                keyframe_reduction: Some(KeyframeReduction::default()),
+                ..Default::default()
+            })],
         },
         Converter {
             name: ".P3M/FRM (Grand Chase)",
-            exporters: vec![Box::<P3mExporter>::default(), Box::<FrmExporter>::default()],
+            name_collision: NameCollision::default(),
+            root_motion,
+            profiles: profiles.to_vec(),
+            exporters: vec![
+                Box::<P3mExporter>::default(),
+                Box::new(FrmExporter {
+                    root_motion,
+                    ..Default::default()
+                }),
+            ],
+        },
+        Converter {
+            name: ".P3M/FRM (Grand Chase, FRM v1.0 for older clients)",
+            name_collision: NameCollision::default(),
+            root_motion,
+            profiles: profiles.to_vec(),
+            exporters: vec![
+                Box::<P3mExporter>::default(),
+                Box::new(FrmExporter {
+                    root_motion,
+                    version: FrmVersion::V1_0,
+                }),
+            ],
         },
     ]
 }
+
+/// Imports a single file into a scene, with the importer that supports its extension. FRM
+/// animations are imported with the given root motion mode, and glTF joints are mapped with the
+/// given skeleton profiles.
+pub fn import_file(
+    path: &str,
+    root_motion: RootMotion,
+    profiles: &[SkeletonProfile],
+) -> Result<Scene> {
+    let asset = Asset::from_path(path).with_context(|| format!("Failed to read \"{}\"", path))?;
+    let extension = asset.extension();
+    let importers = importers(root_motion, profiles);
+    let Some(importer) = importers
+        .iter()
+        .find(|importer| importer.extensions().contains(&extension.as_str()))
+    else {
+        bail!("\"{}\" has an unsupported extension", path);
+    };
+
+    let mut scene = Scene::default();
+    let warnings = importer
+        .import(&asset, &mut scene)
+        .with_context(|| format!("Failed to import \"{}\"", path))?;
+    print_warnings(&warnings);
+    Ok(scene)
+}
+
+/// Exports the animations of a scene into a single FRM or GLB file, depending on the extension of
+/// the path. FRM files store a single animation, whereas GLB files store the whole scene, which
+/// must have a skeleton for the animations to be kept. glTF joints are named with the given
+/// skeleton profiles.
+pub fn export_file(
+    scene: &Scene,
+    path: &str,
+    root_motion: RootMotion,
+    profiles: &[SkeletonProfile],
+) -> Result<()> {
+    let extension = Path::new(path)
+        .extension()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let (assets, warnings) = match extension.to_str() {
+        Some("frm") => {
+            if scene.animations.len() != 1 {
+                bail!(
+                    "FRM files store a single animation, but there are {}",
+                    scene.animations.len()
+                );
+            }
+            FrmExporter {
+                root_motion,
+                ..Default::default()
+            }
+            .export(scene)?
+        }
+        Some("glb") => {
+            if scene.skeleton.is_empty() {
+                bail!("GLB files can only store animations alongside a skeleton");
+            }
+            GltfExporter {
+                profiles: profiles.to_vec(),
+                ..Default::default()
+            }
+            .export(scene)?
+        }
+        _ => bail!("\"{}\" is not a FRM or GLB file", path),
+    };
+    print_warnings(&warnings);
+
+    // Both exporters produce a single file here.
+    let asset = assets.first().context("No files were exported")?;
+    fs::write(path, &asset.bytes).with_context(|| format!("Failed to write \"{}\"", path))
+}
+
+/// Prints the warnings raised while importing or exporting a file.
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// Returns the paths where the files of a single export are written, given the paths of the
+/// files exported before them and what happens when their names collide. Files with the same
+/// name and different extensions, like the ".gltf" and ".bin" files of a model, are renamed
+/// together, and the references of ".gltf" files to the renamed files are updated.
+fn resolve_paths(
+    assets: &mut [Asset],
+    out_path: &Path,
+    used_paths: &HashSet<PathBuf>,
+    name_collision: NameCollision,
+) -> Result<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, asset) in assets.iter().enumerate() {
+        let group = groups.iter_mut().find(|group| {
+            assets[group[0]].name() == asset.name()
+                && group
+                    .iter()
+                    .all(|&other| assets[other].extension() != asset.extension())
+        });
+        match group {
+            Some(group) => group.push(index),
+            None => groups.push(vec![index]),
+        }
+    }
+
+    let mut used_paths = used_paths.clone();
+    let mut paths = vec![PathBuf::new(); assets.len()];
+    for group in groups {
+        let original: Vec<_> = group
+            .iter()
+            .map(|&index| out_path.join(assets[index].path()))
+            .collect();
+        let is_free = |paths: &[PathBuf]| !paths.iter().any(|path| used_paths.contains(path));
+
+        let resolved = if is_free(&original) {
+            original.clone()
+        } else {
+            match name_collision {
+                NameCollision::Suffix => (1..)
+                    .map(|suffix| {
+                        original
+                            .iter()
+                            .map(|path| with_suffix(path, suffix))
+                            .collect::<Vec<_>>()
+                    })
+                    .find(|paths| is_free(paths))
+                    .unwrap(),
+                NameCollision::Overwrite => original.clone(),
+                NameCollision::Fail => {
+                    let path = original.iter().find(|path| used_paths.contains(*path));
+                    bail!(
+                        "another exported file is named \"{}\"",
+                        path.and_then(|path| path.file_name())
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                    )
+                }
+            }
+        };
+
+        if resolved != original {
+            for &index in &group {
+                if assets[index].extension() != "gltf" {
+                    continue;
+                }
+                for (from, to) in original.iter().zip(&resolved) {
+                    assets[index].bytes =
+                        rename_buffer_file(&assets[index].bytes, &file_name(from), &file_name(to))
+                            .context("Failed to rename the files referenced by a glTF file")?;
+                }
+            }
+        }
+
+        for (&index, path) in group.iter().zip(resolved) {
+            used_paths.insert(path.clone());
+            paths[index] = path;
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Returns the given path with a numeric suffix appended to its name (e.g., "mesh_1.p3m" for
+/// "mesh.p3m").
+fn with_suffix(path: &Path, suffix: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}_{}", stem, suffix);
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the skeleton profiles stored in the "profiles" directory next to the executable.
+/// Profiles that cannot be read are skipped with a warning.
+pub fn profiles() -> Vec<SkeletonProfile> {
+    let Some(dir) = env::current_exe()
+        .ok()
+        .and_then(|path| Some(path.parent()?.join(PROFILES_DIR)))
+        .filter(|dir| dir.is_dir())
+    else {
+        return Vec::new();
+    };
+
+    match SkeletonProfile::load_dir(&dir) {
+        Ok(profiles) => profiles
+            .into_iter()
+            .filter_map(|profile| match profile {
+                Ok(profile) => Some(profile),
+                Err(err) => {
+                    eprintln!("Warning: {:#}. The profile was skipped.", err);
+                    None
+                }
+            })
+            .collect(),
+        Err(err) => {
+            eprintln!("Failed to load the skeleton profiles: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn resolve(
+        names: &[&str],
+        used_paths: &HashSet<PathBuf>,
+        name_collision: NameCollision,
+    ) -> Result<Vec<PathBuf>> {
+        let mut assets: Vec<_> = names
+            .iter()
+            .map(|name| Asset::new(Vec::new(), name))
+            .collect();
+        resolve_paths(&mut assets, Path::new("out"), used_paths, name_collision)
+    }
+
+    #[test]
+    fn name_collisions() {
+        let used_paths = HashSet::from([
+            PathBuf::from("out/mesh.p3m"),
+            PathBuf::from("out/mesh_1.p3m"),
+        ]);
+
+        for name_collision in [
+            NameCollision::Suffix,
+            NameCollision::Overwrite,
+            NameCollision::Fail,
+        ] {
+            assert_eq!(
+                vec![PathBuf::from("out/walk.frm")],
+                resolve(&["walk.frm"], &used_paths, name_collision).unwrap(),
+                "{:?}",
+                name_collision
+            );
+        }
+        assert_eq!(
+            vec![
+                PathBuf::from("out/mesh_2.p3m"),
+                PathBuf::from("out/mesh_3.p3m"),
+                PathBuf::from("out/walk"),
+            ],
+            resolve(
+                &["mesh.p3m", "mesh.p3m", "walk"],
+                &used_paths,
+                NameCollision::Suffix
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            vec![PathBuf::from("out/mesh.p3m"), PathBuf::from("out/mesh.p3m")],
+            resolve(
+                &["mesh.p3m", "mesh.p3m"],
+                &HashSet::new(),
+                NameCollision::Overwrite
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            "another exported file is named \"mesh.p3m\"",
+            resolve(&["mesh.p3m"], &used_paths, NameCollision::Fail)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn renamed_gltf_files() {
+        let scene = Scene {
+            meshes: vec![crate::scene::Mesh {
+                name: String::from("red goblin"),
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let (mut assets, _) = GltfExporter {
+            format: GltfFormat::Separate,
+            ..Default::default()
+        }
+        .export(&scene)
+        .unwrap();
+        // Only the ".bin" file collides, but both files are renamed together.
+        let used_paths = HashSet::from([PathBuf::from("out/red goblin.bin")]);
+
+        let paths = resolve_paths(
+            &mut assets,
+            Path::new("out"),
+            &used_paths,
+            NameCollision::Suffix,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                PathBuf::from("out/red goblin_1.gltf"),
+                PathBuf::from("out/red goblin_1.bin"),
+            ],
+            paths
+        );
+        let json: gltf::json::Value =
+            gltf::json::deserialize::from_slice(&assets[0].bytes).unwrap();
+        assert_eq!("red%20goblin_1.bin", json["buffers"][0]["uri"]);
+    }
+}
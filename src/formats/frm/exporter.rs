@@ -1,5 +1,4 @@
-use anyhow::Result;
-use glam::Vec3A;
+use anyhow::{Context, Result};
 
 use crate::{
     asset::Asset,
@@ -8,18 +7,27 @@ use crate::{
     scene::{Animation, Scene},
 };
 
-use super::internal::{Frame, FrmVersion};
+use super::{
+    internal::{Frame, FrmVersion, FRAME_RATE, MAX_POS_Z_NOISE},
+    RootMotion,
+};
 
 #[derive(Default)]
-pub struct FrmExporter {}
+pub struct FrmExporter {
+    /// Specifies how the root translations were converted when the animations were imported.
+    pub root_motion: RootMotion,
+    /// The version of the exported files.
+    pub version: FrmVersion,
+}
 
 impl Exporter for FrmExporter {
-    fn export(&self, scene: &Scene) -> Result<Vec<Asset>> {
+    fn export(&self, scene: &Scene) -> Result<(Vec<Asset>, Vec<String>)> {
         let mut result = Vec::new();
         for animation in &scene.animations {
             let frm = Frm {
-                version: FrmVersion::V1_1,
-                frames: convert_frames(animation),
+                version: self.version,
+                // FRM animations are always played at the same frame rate.
+                frames: convert_frames(&animation.resample(FRAME_RATE), self.root_motion),
             };
 
             let name = if !animation.name.is_empty() {
@@ -27,35 +35,129 @@ impl Exporter for FrmExporter {
             } else {
                 "animation"
             };
-            let asset = Asset::new(frm.to_bytes()?, &format!("anim_{}.frm", name));
+            let bytes = frm
+                .to_bytes()
+                .with_context(|| format!("Failed to export animation \"{}\"", name))?;
+            let asset = Asset::new(bytes, &format!("anim_{}.frm", name));
 
             result.push(asset);
         }
-        Ok(result)
+        Ok((result, Vec::new()))
     }
 }
 
-// The algorithm assumes the animation keyframes are already sampled at 55 FPS.
-fn convert_frames(animation: &Animation) -> Vec<Frame> {
-    let mut prev_root_trans = Vec3A::new(0., 0., 0.);
+// The algorithm assumes the animation keyframes are already sampled at the FRM frame rate.
+fn convert_frames(animation: &Animation, root_motion: RootMotion) -> Vec<Frame> {
+    let mut prev_x = 0.;
     animation
         .frames
         .iter()
         .map(|keyframe| {
-            let frame = Frame {
-                option: 0,
-                plus_x: keyframe.translation.x - prev_root_trans.x,
-                pos_y: keyframe.translation.y,
-                pos_z: keyframe.translation.z - prev_root_trans.z,
+            let position = keyframe.translation + keyframe.root_motion;
+            let (plus_x, pos_z) = match root_motion {
+                RootMotion::AsIs => (position.x, position.z),
+                RootMotion::Root | RootMotion::Separate => (position.x - prev_x, position.z),
+                RootMotion::InPlace => (0., 0.),
+            };
+            prev_x = position.x;
+            // Noise along the Z axis is written as 0, so that it isn't mistaken for root motion.
+            let pos_z = if pos_z.abs() > MAX_POS_Z_NOISE {
+                pos_z
+            } else {
+                0.
+            };
+
+            Frame {
+                option: keyframe.option,
+                plus_x,
+                pos_y: position.y,
+                pos_z,
                 bones: keyframe
                     .transforms
                     .iter()
                     .map(|matrix| matrix.to_cols_array_2d())
                     .collect(),
-            };
-            prev_root_trans = keyframe.translation;
-
-            frame
+            }
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use glam::{Mat4, Vec3A};
+
+    use crate::{conversion::Importer, formats::FrmImporter, scene::Keyframe};
+
+    #[test]
+    fn round_trip() {
+        let bytes = Frm {
+            version: FrmVersion::V1_1,
+            frames: [(0, 1., 2., 3.), (3, 4., 5., 6.), (0, -2., 1., 0.5)]
+                .into_iter()
+                .map(|(option, plus_x, pos_y, pos_z)| Frame {
+                    option,
+                    plus_x,
+                    pos_y,
+                    pos_z,
+                    bones: vec![[[1.; 4]; 4]],
+                })
+                .collect(),
+        }
+        .to_bytes()
+        .unwrap();
+        let asset = Asset::new(bytes.clone(), "walk.frm");
+
+        for root_motion in [RootMotion::AsIs, RootMotion::Root, RootMotion::Separate] {
+            let mut scene = Scene::default();
+            FrmImporter { root_motion }
+                .import(&asset, &mut scene)
+                .unwrap();
+            let (assets, _) = FrmExporter {
+                root_motion,
+                ..Default::default()
+            }
+            .export(&scene)
+            .unwrap();
+
+            assert_eq!(bytes, assets[0].bytes, "{:?}", root_motion);
+        }
+    }
+
+    #[test]
+    fn z_noise() {
+        let scene = Scene {
+            animations: vec![Animation {
+                name: String::from("walk"),
+                sampling_rate: FRAME_RATE,
+                frames: [0., 2e-5, -3e-6]
+                    .into_iter()
+                    .map(|z| Keyframe {
+                        translation: Vec3A::new(0., 1., z),
+                        transforms: vec![Mat4::IDENTITY],
+                        ..Default::default()
+                    })
+                    .collect(),
+            }],
+            ..Default::default()
+        };
+
+        for version in [FrmVersion::V1_0, FrmVersion::V1_1] {
+            let (assets, _) = FrmExporter {
+                version,
+                ..Default::default()
+            }
+            .export(&scene)
+            .unwrap();
+
+            let frm = Frm::from_bytes(&assets[0].bytes).unwrap();
+            assert!(
+                frm.frames.iter().all(|frame| frame.pos_z == 0.),
+                "{}",
+                version
+            );
+        }
+    }
+}
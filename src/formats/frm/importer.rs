@@ -7,23 +7,30 @@ use crate::{
     scene::{Animation, Keyframe, Scene},
 };
 
-use super::internal::Frm;
+use super::{
+    internal::{Frm, FRAME_RATE},
+    RootMotion,
+};
 
 #[derive(Default)]
-pub struct FrmImporter {}
+pub struct FrmImporter {
+    /// Specifies how the root translations are converted.
+    pub root_motion: RootMotion,
+}
 
 impl Importer for FrmImporter {
-    fn import(&self, asset: &Asset, scene: &mut Scene) -> anyhow::Result<()> {
+    fn import(&self, asset: &Asset, scene: &mut Scene) -> anyhow::Result<Vec<String>> {
         let frm = Frm::from_bytes(&asset.bytes)
             .context("Failed to deserialize the bytes of the FRM asset")?;
 
         let animation = Animation {
             name: asset.name().to_string(),
-            frames: convert_frames(&frm),
+            sampling_rate: FRAME_RATE,
+            frames: convert_frames(&frm, self.root_motion),
         };
         scene.animations.push(animation);
 
-        Ok(())
+        Ok(Vec::new())
     }
 
     fn extensions(&self) -> &[&str] {
@@ -31,25 +38,32 @@ impl Importer for FrmImporter {
     }
 }
 
-fn convert_frames(frm: &Frm) -> Vec<Keyframe> {
-    let mut prev_root_trans = Vec3A::new(0., 0., 0.);
-    let mut current_time = 0.;
+fn convert_frames(frm: &Frm, root_motion: RootMotion) -> Vec<Keyframe> {
+    let mut x = 0.;
 
     frm.frames
         .iter()
         .map(|frame| {
-            let keyframe = Keyframe {
-                // The Z coordinate is set to zero because it is unused in the game and usually
-                // contains unreasonable values.
-                translation: Vec3A::new(prev_root_trans.x + frame.plus_x, frame.pos_y, frame.pos_z),
-                transforms: frame.bones.iter().map(Mat4::from_cols_array_2d).collect(),
+            // Only the X coordinate is relative to the previous frame.
+            x += frame.plus_x;
+            let position = Vec3A::new(x, frame.pos_y, frame.pos_z);
+            let vertical = Vec3A::new(0., frame.pos_y, 0.);
+
+            let (translation, root_motion) = match root_motion {
+                RootMotion::AsIs => (
+                    Vec3A::new(frame.plus_x, frame.pos_y, frame.pos_z),
+                    Vec3A::ZERO,
+                ),
+                RootMotion::Root => (position, Vec3A::ZERO),
+                RootMotion::InPlace => (vertical, Vec3A::ZERO),
+                RootMotion::Separate => (vertical, position - vertical),
             };
-
-            // The frame rate of the animation is always 55 FPS.
-            current_time += 1. / 55.;
-            prev_root_trans = keyframe.translation;
-
-            keyframe
+            Keyframe {
+                translation,
+                root_motion,
+                option: frame.option,
+                transforms: frame.bones.iter().map(Mat4::from_cols_array_2d).collect(),
+            }
         })
         .collect()
 }
@@ -84,10 +98,12 @@ mod tests {
             ],
         };
 
-        let actual = convert_frames(&frm);
+        let actual = convert_frames(&frm, RootMotion::Root);
         let expected = vec![
             Keyframe {
                 translation: Vec3A::new(1., 1., 1.),
+                root_motion: Vec3A::ZERO,
+                option: 0,
                 transforms: vec![
                     Mat4::from_cols_array(&[1.; 16]),
                     Mat4::from_cols_array(&[2.; 16]),
@@ -95,6 +111,8 @@ mod tests {
             },
             Keyframe {
                 translation: Vec3A::new(2., 1., 1.),
+                root_motion: Vec3A::ZERO,
+                option: 0,
                 transforms: vec![
                     Mat4::from_cols_array(&[3.; 16]),
                     Mat4::from_cols_array(&[4.; 16]),
@@ -104,4 +122,54 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn root_motion() {
+        let frame = |plus_x: f32, pos_y: f32, pos_z: f32| Frame {
+            option: 0,
+            plus_x,
+            pos_y,
+            pos_z,
+            bones: Vec::new(),
+        };
+        let frm = Frm {
+            version: FrmVersion::V1_1,
+            frames: vec![frame(1., 2., 3.), frame(4., 5., 6.)],
+        };
+
+        let translations = |root_motion: RootMotion| -> Vec<_> {
+            convert_frames(&frm, root_motion)
+                .iter()
+                .map(|keyframe| (keyframe.translation, keyframe.root_motion))
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                (Vec3A::new(1., 2., 3.), Vec3A::ZERO),
+                (Vec3A::new(4., 5., 6.), Vec3A::ZERO)
+            ],
+            translations(RootMotion::AsIs)
+        );
+        assert_eq!(
+            vec![
+                (Vec3A::new(1., 2., 3.), Vec3A::ZERO),
+                (Vec3A::new(5., 5., 6.), Vec3A::ZERO)
+            ],
+            translations(RootMotion::Root)
+        );
+        assert_eq!(
+            vec![
+                (Vec3A::new(0., 2., 0.), Vec3A::ZERO),
+                (Vec3A::new(0., 5., 0.), Vec3A::ZERO)
+            ],
+            translations(RootMotion::InPlace)
+        );
+        assert_eq!(
+            vec![
+                (Vec3A::new(0., 2., 0.), Vec3A::new(1., 0., 3.)),
+                (Vec3A::new(0., 5., 0.), Vec3A::new(5., 0., 6.))
+            ],
+            translations(RootMotion::Separate)
+        );
+    }
 }
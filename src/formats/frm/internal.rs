@@ -1,8 +1,19 @@
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::{
+    fmt,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 
+/// The frame rate at which FRM animations are played, in frames per second.
+pub const FRAME_RATE: f32 = 55.;
+
+/// The largest position along the Z axis that is considered noise from resampling or coordinate
+/// conversions, rather than root motion. Frames with smaller positions can be stored in FRM v1.0
+/// files, which have no Z positions.
+pub const MAX_POS_Z_NOISE: f32 = 1e-4;
+
 const VERSION_HEADER: &str = "Frm Ver 1.1\0";
 
 /// Represents an FRM file. The FRM format stores keyframe animation data from GrandChase.
@@ -61,6 +72,7 @@ impl Frm {
     }
 
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.validate()?;
         let mut bytes = Vec::new();
 
         match self.version {
@@ -89,6 +101,48 @@ impl Frm {
         Ok(bytes)
     }
 
+    /// Checks whether the animation fits the limits of its version.
+    pub fn validate(&self) -> Result<()> {
+        let max_count = match self.version {
+            FrmVersion::V1_0 => u8::MAX as usize,
+            FrmVersion::V1_1 => u16::MAX as usize,
+        };
+        if self.frames.len() > max_count {
+            return Err(anyhow!(
+                "FRM {} supports up to {} frames, but the animation has {}",
+                self.version,
+                max_count,
+                self.frames.len()
+            ));
+        }
+        if self.num_bones() > max_count {
+            return Err(anyhow!(
+                "FRM {} supports up to {} bones, but the animation has {}",
+                self.version,
+                max_count,
+                self.num_bones()
+            ));
+        }
+
+        if self.version == FrmVersion::V1_0 {
+            if let Some(index) = self
+                .frames
+                .iter()
+                .position(|frame| frame.pos_z.abs() > MAX_POS_Z_NOISE)
+            {
+                return Err(anyhow!(
+                    "FRM {} can't store root motion along the Z axis, but frame {} is at Z = {}. \
+                    Export to FRM v1.1 or keep the animation in place instead",
+                    self.version,
+                    index,
+                    self.frames[index].pos_z
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn num_bones(&self) -> usize {
         match self.frames.first() {
             Some(frame) => frame.bones.len(),
@@ -100,7 +154,7 @@ impl Frm {
 /// Represents an animation keyframe.
 #[derive(Debug, PartialEq)]
 pub struct Frame {
-    /// Unused field. It's defaulted to `0`.
+    /// Flags of the frame that the game client reacts to. It's `0` for most frames.
     pub option: u8,
     /// The x-coordinate of the root position of the skeleton for the current frame, **relative to
     /// the previous frame**.
@@ -169,12 +223,24 @@ impl Default for Frame {
 }
 
 /// Specifies the version and format of the FRM file.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum FrmVersion {
+    /// The original format, which is still the only one read by some older clients and servers.
+    /// It supports up to 255 frames and bones, and has no Z coordinate.
     V1_0,
+    #[default]
     V1_1,
 }
 
+impl fmt::Display for FrmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrmVersion::V1_0 => write!(f, "v1.0"),
+            FrmVersion::V1_1 => write!(f, "v1.1"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
@@ -290,4 +356,48 @@ mod tests {
 
         (frm, &DATA)
     }
+
+    #[test]
+    fn validate_v1_0() {
+        let frame = |pos_z: f32, num_bones: usize| Frame {
+            pos_z,
+            bones: vec![[[0.; 4]; 4]; num_bones],
+            ..Default::default()
+        };
+        let frm = |frames: Vec<Frame>| Frm {
+            version: FrmVersion::V1_0,
+            frames,
+        };
+
+        assert!(frm(vec![frame(0., 255)]).validate().is_ok());
+        assert!(frm((0..255).map(|_| frame(0., 1)).collect())
+            .validate()
+            .is_ok());
+
+        let error = frm((0..256).map(|_| frame(0., 1)).collect())
+            .to_bytes()
+            .unwrap_err();
+        assert_eq!(
+            "FRM v1.0 supports up to 255 frames, but the animation has 256",
+            error.to_string()
+        );
+        let error = frm(vec![frame(0., 256)]).validate().unwrap_err();
+        assert_eq!(
+            "FRM v1.0 supports up to 255 bones, but the animation has 256",
+            error.to_string()
+        );
+        assert!(frm(vec![frame(1e-6, 1), frame(-1e-5, 1)])
+            .validate()
+            .is_ok());
+        let error = frm(vec![frame(0., 1), frame(0.5, 1)])
+            .validate()
+            .unwrap_err();
+        assert!(error.to_string().contains("frame 1 is at Z = 0.5"));
+
+        let v1_1 = Frm {
+            version: FrmVersion::V1_1,
+            frames: vec![frame(0.5, 256)],
+        };
+        assert!(v1_1.validate().is_ok());
+    }
 }
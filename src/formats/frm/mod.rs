@@ -1,5 +1,27 @@
-pub use {exporter::FrmExporter, importer::FrmImporter};
+pub use {exporter::FrmExporter, importer::FrmImporter, internal::FrmVersion};
 
 pub mod exporter;
 pub mod importer;
 pub mod internal;
+
+/// Specifies how the root translations of FRM animations, which move the whole skeleton, are
+/// converted. When exporting, the scene is assumed to have been imported with the same mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RootMotion {
+    /// The translations are kept as they are stored in the file: the X coordinate is an offset
+    /// from the previous frame, whereas Y and Z are positions.
+    AsIs,
+    /// The translations are converted into positions of the root node, so that the skeleton
+    /// moves like in the game.
+    #[default]
+    Root,
+    /// The horizontal motion (X and Z) is removed, so that the skeleton stays in place. It's
+    /// also removed when exporting.
+    InPlace,
+    /// The horizontal motion is moved to [`Keyframe::root_motion`], so that the skeleton stays
+    /// in place but the motion is preserved. When exporting, the motion is added back to the
+    /// root translations.
+    ///
+    /// [`Keyframe::root_motion`]: crate::scene::Keyframe::root_motion
+    Separate,
+}
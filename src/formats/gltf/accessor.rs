@@ -0,0 +1,152 @@
+use gltf::accessor::{DataType, Item, Iter};
+
+/// Reads the elements of an accessor as floating point vectors. Sparse accessors are supported.
+///
+/// Integer components, allowed for vertex attributes by `KHR_mesh_quantization`, are converted
+/// into floats. If the accessor is normalized, they are mapped to the `[0, 1]` range (unsigned)
+/// or the `[-1, 1]` range (signed). Returns `None` if the accessor cannot be read.
+pub fn read_floats<const N: usize>(
+    accessor: gltf::Accessor,
+    buffers: &[Vec<u8>],
+) -> Option<Vec<[f32; N]>>
+where
+    [f32; N]: Item,
+    [i8; N]: Item,
+    [u8; N]: Item,
+    [i16; N]: Item,
+    [u16; N]: Item,
+{
+    match accessor.data_type() {
+        DataType::F32 => read_components::<f32, N>(accessor, buffers),
+        DataType::I8 => read_components::<i8, N>(accessor, buffers),
+        DataType::U8 => read_components::<u8, N>(accessor, buffers),
+        DataType::I16 => read_components::<i16, N>(accessor, buffers),
+        DataType::U16 => read_components::<u16, N>(accessor, buffers),
+        DataType::U32 => None,
+    }
+}
+
+fn read_components<T: Component, const N: usize>(
+    accessor: gltf::Accessor,
+    buffers: &[Vec<u8>],
+) -> Option<Vec<[f32; N]>>
+where
+    [T; N]: Item,
+{
+    let normalized = accessor.normalized();
+    let iter: Iter<[T; N]> = Iter::new(accessor, |buffer| {
+        buffers.get(buffer.index()).map(Vec::as_slice)
+    })?;
+    Some(
+        iter.map(|element| element.map(|component| component.to_f32(normalized)))
+            .collect(),
+    )
+}
+
+/// A component type of an accessor.
+trait Component: Copy {
+    fn to_f32(self, normalized: bool) -> f32;
+}
+
+impl Component for f32 {
+    fn to_f32(self, _normalized: bool) -> f32 {
+        self
+    }
+}
+
+macro_rules! impl_integer_component {
+    ($($ty:ty),*) => {
+        $(
+            impl Component for $ty {
+                fn to_f32(self, normalized: bool) -> f32 {
+                    if normalized {
+                        (self as f32 / <$ty>::MAX as f32).max(-1.)
+                    } else {
+                        self as f32
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_component!(i8, u8, i16, u16);
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn read(json: &str, buffer: &[u8]) -> Option<Vec<[f32; 2]>> {
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+        let accessor = gltf.accessors().next().unwrap();
+        read_floats::<2>(accessor, &[buffer.to_vec()])
+    }
+
+    #[test]
+    fn quantized() {
+        let buffer: Vec<u8> = [-32767i16, 0, 32767, 100]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let json = |normalized: bool| {
+            format!(
+                r#"{{
+                    "asset": {{ "version": "2.0" }},
+                    "accessors": [{{
+                        "bufferView": 0, "componentType": 5122, "count": 2, "type": "VEC2",
+                        "normalized": {}
+                    }}],
+                    "bufferViews": [{{ "buffer": 0, "byteLength": 8 }}],
+                    "buffers": [{{ "byteLength": 8 }}]
+                }}"#,
+                normalized
+            )
+        };
+
+        assert_eq!(
+            Some(vec![[-1., 0.], [1., 100. / 32767.]]),
+            read(&json(true), &buffer)
+        );
+        assert_eq!(
+            Some(vec![[-32767., 0.], [32767., 100.]]),
+            read(&json(false), &buffer)
+        );
+    }
+
+    #[test]
+    fn sparse() {
+        let mut buffer = Vec::new();
+        for value in [1f32, 2., 3., 4., 5., 6.] {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        buffer.extend_from_slice(&2u16.to_le_bytes());
+        buffer.extend_from_slice(&[0; 2]);
+        for value in [7f32, 8.] {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC2",
+                "sparse": {
+                    "count": 1,
+                    "indices": { "bufferView": 1, "componentType": 5123 },
+                    "values": { "bufferView": 2 }
+                }
+            }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 24 },
+                { "buffer": 0, "byteOffset": 24, "byteLength": 2 },
+                { "buffer": 0, "byteOffset": 28, "byteLength": 8 }
+            ],
+            "buffers": [{ "byteLength": 36 }]
+        }"#;
+
+        assert_eq!(
+            Some(vec![[1., 2.], [3., 4.], [7., 8.]]),
+            read(json, &buffer)
+        );
+    }
+}
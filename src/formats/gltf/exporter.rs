@@ -1,8 +1,12 @@
-use std::{collections::HashMap, mem};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    mem,
+    ops::Mul,
+};
 
 use anyhow::Result;
 use byteorder::{WriteBytesExt, LE};
-use glam::Mat4;
+use glam::{Mat4, Quat, Vec2, Vec3, Vec3A};
 use gltf::{
     json::{
         self,
@@ -15,31 +19,180 @@ use gltf::{
 use crate::{
     asset::Asset,
     conversion::Exporter,
+    profile::SkeletonProfile,
     scene::{Animation, Joint, Mesh, Scene},
 };
 
+use super::{keyframes, meshopt, uri, FRAME_OPTIONS_NODE_NAME, ROOT_MOTION_NODE_NAME};
+
+/// The tolerance used to decide whether joint translations and scales are animated.
+const EPSILON: f32 = 1e-5;
+
+/// The tolerance used to report joint matrices with scale or shear. FRM matrices accumulate
+/// rounding errors, so smaller deviations are expected.
+const MATRIX_TOLERANCE: f32 = 1e-3;
+
+const QUANTIZATION_EXTENSION_NAME: &str = "KHR_mesh_quantization";
+
 #[derive(Default)]
-pub struct GltfExporter {}
+pub struct GltfExporter {
+    /// The container format of the exported files.
+    pub format: GltfFormat,
+    /// The skeleton profiles used to name the joint nodes. The first profile that matches the
+    /// skeleton of the scene is used. Otherwise, joints are named "bone_N".
+    pub profiles: Vec<SkeletonProfile>,
+    /// Store vertex attributes and joint rotations as integers (`KHR_mesh_quantization`), which
+    /// take less space than floats at the cost of some precision.
+    pub quantize: bool,
+    /// Specifies how the buffer is compressed.
+    pub compression: GltfCompression,
+    /// Removes the animation keyframes that can be interpolated from the remaining ones within
+    /// the given tolerance. If it's `None`, every frame is exported.
+    pub keyframe_reduction: Option<KeyframeReduction>,
+    /// Specifies how the animation keyframes are interpolated.
+    pub interpolation: GltfInterpolation,
+}
+
+/// Specifies how the glTF data is laid out on disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GltfFormat {
+    /// A single binary `.glb` file with the JSON and the buffer embedded.
+    #[default]
+    Binary,
+    /// A `.gltf` JSON file that references an external `.bin` buffer.
+    Separate,
+}
+
+/// Specifies how the exported animations are interpolated between keyframes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GltfInterpolation {
+    /// The keyframes are interpolated linearly.
+    #[default]
+    Linear,
+    /// The keyframes are interpolated with cubic splines, whose tangents are estimated from the
+    /// neighbouring frames. Grand Chase interpolates its animations with bezier curves, so this
+    /// is closer to how they look in the game.
+    CubicSpline,
+}
+
+/// The maximum error allowed when removing animation keyframes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyframeReduction {
+    /// The maximum angle between the exported and the original joint rotations, in radians.
+    pub max_angle: f32,
+    /// The maximum distance between the exported and the original translations and scales.
+    pub max_distance: f32,
+}
+
+impl Default for KeyframeReduction {
+    fn default() -> Self {
+        Self {
+            max_angle: 1e-3,
+            max_distance: 1e-3,
+        }
+    }
+}
+
+/// Specifies how the buffer of the exported files is compressed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GltfCompression {
+    /// The buffer is not compressed.
+    #[default]
+    None,
+    /// The buffer views are compressed with `EXT_meshopt_compression`, and loaders that don't
+    /// support the extension can't read the files.
+    Meshopt,
+    /// The buffer views are compressed with `EXT_meshopt_compression`, and the uncompressed data
+    /// is kept as a fallback for loaders that don't support the extension.
+    MeshoptWithFallback,
+}
 
 // https://www.khronos.org/registry/glTF/specs/2.0/glTF-2.0.html
 impl Exporter for GltfExporter {
-    fn export(&self, scene: &Scene) -> Result<Vec<Asset>> {
+    fn export(&self, scene: &Scene) -> Result<(Vec<Asset>, Vec<String>)> {
         let mut root = json::Root::default();
         let mut buffer = Vec::new();
+        let mut warnings = Vec::new();
 
         let scene = super::transform(scene);
-
-        let skeleton_index = insert_scene(&mut root, &scene.skeleton, &scene.meshes);
-        insert_meshes(&mut root, &mut buffer, &scene.meshes)?;
-        insert_skins(&mut root, &mut buffer, &scene, skeleton_index)?;
+        // Quantized positions are restored by the inverse bind matrices, so they are only
+        // quantized when there is a skin.
+        let quantization = (self.quantize && !scene.skeleton.is_empty())
+            .then(|| PositionQuantization::new(&scene.meshes));
+
+        let profile = SkeletonProfile::find(&self.profiles, &scene.skeleton);
+        let skeleton_index = insert_scene(&mut root, &scene.skeleton, &scene.meshes, profile);
+        // The inverse bind matrix of the skeleton root only restores quantized positions, so
+        // unskinned vertices are bound to it.
+        let unskinned_joint = quantization.as_ref().map(|_| scene.skeleton.len());
+        insert_meshes(
+            &mut root,
+            &mut buffer,
+            &scene.meshes,
+            self.quantize,
+            quantization.as_ref(),
+            unskinned_joint,
+        )?;
+        insert_skins(
+            &mut root,
+            &mut buffer,
+            &scene,
+            skeleton_index,
+            quantization.as_ref(),
+        )?;
+        let frames = || {
+            scene
+                .animations
+                .iter()
+                .flat_map(|animation| &animation.frames)
+        };
+        let nodes = AnimationNodes {
+            skeleton: skeleton_index,
+            root_motion: frames()
+                .any(|frame| frame.root_motion != Vec3A::ZERO)
+                .then(|| insert_top_level_node(&mut root, ROOT_MOTION_NODE_NAME)),
+            frame_options: frames()
+                .any(|frame| frame.option != 0)
+                .then(|| insert_top_level_node(&mut root, FRAME_OPTIONS_NODE_NAME)),
+        };
         insert_animations(
             &mut root,
             &mut buffer,
             &scene.animations,
-            scene.skeleton.len(),
-            skeleton_index,
+            &scene.skeleton,
+            nodes,
+            self,
+            &mut warnings,
         )?;
-        insert_buffers(&mut root, &buffer);
+        if self.quantize {
+            root.extensions_used
+                .push(String::from(QUANTIZATION_EXTENSION_NAME));
+            root.extensions_required
+                .push(String::from(QUANTIZATION_EXTENSION_NAME));
+        }
+
+        let name = if let Some(mesh) = scene.meshes.first() {
+            &mesh.name
+        } else if let Some(animation) = scene.animations.first() {
+            &animation.name
+        } else {
+            "model"
+        };
+
+        let buffer_uri = match self.format {
+            GltfFormat::Binary => None,
+            GltfFormat::Separate => Some(uri::encode(&format!("{}.bin", name))),
+        };
+        let (buffer, extensions) = match self.compression {
+            GltfCompression::None => {
+                insert_buffers(&mut root, &buffer, buffer_uri);
+                (buffer, MeshoptExtensions::default())
+            }
+            GltfCompression::Meshopt => compress_buffer(&mut root, buffer, buffer_uri, false)?,
+            GltfCompression::MeshoptWithFallback => {
+                compress_buffer(&mut root, buffer, buffer_uri, true)?
+            }
+        };
 
         root.asset = json::Asset {
             generator: Some(format!(
@@ -50,27 +203,33 @@ impl Exporter for GltfExporter {
             ..Default::default()
         };
 
-        let json_string = json::serialize::to_string(&root)?;
-        let bytes = Glb {
-            header: gltf::binary::Header {
-                magic: *b"glTF",
-                version: 2,
-                length: calculate_length(&json_string, &buffer) as u32,
-            },
-            json: json_string.into_bytes().into(),
-            bin: Some(buffer.into()),
+        match self.format {
+            GltfFormat::Binary => {
+                let json_string = serialize_json(&root, &extensions, false)?;
+                let bytes = Glb {
+                    header: gltf::binary::Header {
+                        magic: *b"glTF",
+                        version: 2,
+                        length: calculate_length(&json_string, &buffer) as u32,
+                    },
+                    json: json_string.into_bytes().into(),
+                    bin: Some(buffer.into()),
+                }
+                .to_vec()?;
+
+                Ok((vec![Asset::new(bytes, &format!("{}.glb", name))], warnings))
+            }
+            GltfFormat::Separate => {
+                // The JSON is pretty-printed so that the files can be reviewed and diffed.
+                let json_string = serialize_json(&root, &extensions, true)?;
+
+                let assets = vec![
+                    Asset::new(json_string.into_bytes(), &format!("{}.gltf", name)),
+                    Asset::new(buffer, &format!("{}.bin", name)),
+                ];
+                Ok((assets, warnings))
+            }
         }
-        .to_vec()?;
-
-        let name = if let Some(mesh) = scene.meshes.first() {
-            &mesh.name
-        } else if let Some(animation) = scene.animations.first() {
-            &animation.name
-        } else {
-            "model"
-        };
-
-        Ok(vec![Asset::new(bytes, &format!("{}.glb", name))])
     }
 }
 
@@ -88,10 +247,15 @@ fn calculate_length(json: &str, bin: &[u8]) -> usize {
 
 /// Converts and inserts the scene and its nodes into the json.
 /// Returns the index of the root node of the skeleton in the node hierarchy.
-fn insert_scene(root: &mut json::Root, skeleton: &[Joint], meshes: &[Mesh]) -> usize {
+fn insert_scene(
+    root: &mut json::Root,
+    skeleton: &[Joint],
+    meshes: &[Mesh],
+    profile: Option<&SkeletonProfile>,
+) -> usize {
     let mut nodes = Vec::new();
 
-    let skeleton_node = push_skeleton_nodes(&mut root.nodes, skeleton);
+    let skeleton_node = push_skeleton_nodes(&mut root.nodes, skeleton, profile);
     nodes.push(skeleton_node);
     for (index, mesh) in meshes.iter().enumerate() {
         let mesh_node = push_mesh_node(&mut root.nodes, mesh, index as u32);
@@ -112,7 +276,33 @@ fn insert_scene(root: &mut json::Root, skeleton: &[Joint], meshes: &[Mesh]) -> u
     skeleton_node
 }
 
-fn push_skeleton_nodes(nodes: &mut Vec<json::Node>, skeleton: &[Joint]) -> usize {
+/// Inserts a node without children at the top of the scene hierarchy.
+fn insert_top_level_node(root: &mut json::Root, name: &str) -> usize {
+    root.nodes.push(json::Node {
+        name: Some(String::from(name)),
+        children: None,
+        translation: None,
+        camera: None,
+        extensions: None,
+        matrix: None,
+        mesh: None,
+        rotation: None,
+        scale: None,
+        skin: None,
+        weights: None,
+        extras: Default::default(),
+    });
+
+    let index = root.nodes.len() - 1;
+    root.scenes[0].nodes.push(json::Index::new(index as u32));
+    index
+}
+
+fn push_skeleton_nodes(
+    nodes: &mut Vec<json::Node>,
+    skeleton: &[Joint],
+    profile: Option<&SkeletonProfile>,
+) -> usize {
     let mut roots = Vec::new();
 
     let offset = nodes.len() as u32;
@@ -122,7 +312,12 @@ fn push_skeleton_nodes(nodes: &mut Vec<json::Node>, skeleton: &[Joint]) -> usize
         }
 
         nodes.push(json::Node {
-            name: Some(format!("bone_{}", index)),
+            name: Some(
+                match profile.and_then(|profile| profile.joint_name(index)) {
+                    Some(name) => name.to_string(),
+                    None => format!("bone_{}", index),
+                },
+            ),
             children: if !joint.children.is_empty() {
                 Some(
                     joint
@@ -189,12 +384,16 @@ fn insert_skins(
     buffer: &mut Vec<u8>,
     scene: &Scene,
     skeleton_index: usize,
+    quantization: Option<&PositionQuantization>,
 ) -> Result<()> {
     if scene.skeleton.is_empty() {
         return Ok(());
     }
 
-    let inverse_bind_accessor = insert_inverse_bind_bytes(root, buffer, scene)?;
+    let dequantization = quantization.map_or(Mat4::IDENTITY, |quantization| {
+        quantization.dequantization_matrix()
+    });
+    let inverse_bind_accessor = insert_inverse_bind_bytes(root, buffer, scene, dequantization)?;
     root.skins = vec![json::Skin {
         inverse_bind_matrices: Some(json::Index::new(inverse_bind_accessor as u32)),
         joints: (0..scene.skeleton.len())
@@ -213,13 +412,23 @@ fn insert_skins(
     Ok(())
 }
 
-fn insert_meshes(root: &mut json::Root, buffer: &mut Vec<u8>, meshes: &[Mesh]) -> Result<()> {
+/// Inserts the meshes. Unskinned vertices are bound to `unskinned_joint` with full weight, or
+/// have no weights if it's `None`.
+fn insert_meshes(
+    root: &mut json::Root,
+    buffer: &mut Vec<u8>,
+    meshes: &[Mesh],
+    quantize: bool,
+    quantization: Option<&PositionQuantization>,
+    unskinned_joint: Option<usize>,
+) -> Result<()> {
     for mesh in meshes {
-        let positions_accessor = insert_positions_bytes(root, buffer, mesh)?;
-        let normals_accessor = insert_normals_bytes(root, buffer, mesh)?;
-        let uv_accessor = insert_uv_bytes(root, buffer, mesh)?;
-        let joints_accessor = insert_joints_bytes(root, buffer, mesh)?;
-        let weights_accessor = insert_weights_bytes(root, buffer, mesh)?;
+        let positions_accessor = insert_positions_bytes(root, buffer, mesh, quantization)?;
+        let normals_accessor = insert_normals_bytes(root, buffer, mesh, quantize)?;
+        let uv_accessor = insert_uv_bytes(root, buffer, mesh, quantize)?;
+        let joints_accessor = insert_joints_bytes(root, buffer, mesh, unskinned_joint)?;
+        let weights_accessor =
+            insert_weights_bytes(root, buffer, mesh, quantize, unskinned_joint.is_some())?;
         let indices_accessor = insert_indices_bytes(root, buffer, mesh)?;
 
         let mut attributes = HashMap::new();
@@ -264,141 +473,626 @@ fn insert_meshes(root: &mut json::Root, buffer: &mut Vec<u8>, meshes: &[Mesh]) -
     Ok(())
 }
 
-fn insert_buffers(root: &mut json::Root, buffer: &[u8]) {
+/// Inserts the binary buffer into the json. When `uri` is `None`, the buffer is expected to be
+/// stored in the binary chunk of a GLB file.
+fn insert_buffers(root: &mut json::Root, buffer: &[u8], uri: Option<String>) {
     root.buffers.push(json::Buffer {
         byte_length: buffer.len() as u32,
-        uri: None,
+        uri,
         name: None,
         extensions: None,
         extras: Default::default(),
     });
 }
 
+/// Compresses every buffer view with `EXT_meshopt_compression` and inserts the buffers into the
+/// json. Returns the data of the first buffer and the extension objects to add to the json.
+///
+/// With a fallback, the compressed data follows the uncompressed data in a single buffer.
+/// Otherwise, the first buffer only holds the compressed data, and the buffer views point to a
+/// second buffer without data that receives the decompressed data.
+fn compress_buffer(
+    root: &mut json::Root,
+    mut buffer: Vec<u8>,
+    uri: Option<String>,
+    fallback: bool,
+) -> Result<(Vec<u8>, MeshoptExtensions)> {
+    let index_views: HashSet<_> = root
+        .meshes
+        .iter()
+        .flat_map(|mesh| &mesh.primitives)
+        .filter_map(|primitive| primitive.indices)
+        .filter_map(|accessor| root.accessors[accessor.value()].buffer_view)
+        .map(|view| view.value())
+        .collect();
+
+    let mut compressed = Vec::new();
+    let mut extensions = MeshoptExtensions::default();
+    for (index, view) in root.buffer_views.iter().enumerate() {
+        let offset = view.byte_offset.unwrap_or_default() as usize;
+        let data = &buffer[offset..offset + view.byte_length as usize];
+
+        let (mode, stride, encoded) = if index_views.contains(&index) {
+            let indices: Vec<_> = data
+                .chunks_exact(2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as u32)
+                .collect();
+            let encoded = meshopt::encode_index_buffer(&indices)?;
+            (meshopt::Mode::Triangles, mem::size_of::<u16>(), encoded)
+        } else {
+            let stride = element_size(root, index);
+            let encoded = meshopt::encode_vertex_buffer(data, stride)?;
+            (meshopt::Mode::Attributes, stride, encoded)
+        };
+
+        align_to(&mut compressed, 4);
+        extensions.views.push(CompressedView {
+            view: index,
+            byte_offset: compressed.len(),
+            byte_length: encoded.len(),
+            byte_stride: stride,
+            count: data.len() / stride,
+            mode,
+        });
+        compressed.extend(encoded);
+    }
+
+    let extension_name = String::from(meshopt::EXTENSION_NAME);
+    root.extensions_used.push(extension_name.clone());
+    if fallback {
+        align_to(&mut buffer, 4);
+        for view in &mut extensions.views {
+            view.byte_offset += buffer.len();
+        }
+        buffer.append(&mut compressed);
+        insert_buffers(root, &buffer, uri);
+        Ok((buffer, extensions))
+    } else {
+        root.extensions_required.push(extension_name);
+        insert_buffers(root, &compressed, uri);
+        insert_buffers(root, &buffer, None);
+        extensions.fallback_buffer = Some(1);
+        for view in &mut root.buffer_views {
+            view.buffer = json::Index::new(1);
+        }
+        Ok((compressed, extensions))
+    }
+}
+
+/// Returns the size of the elements of a buffer view, given by its stride or its accessor.
+fn element_size(root: &json::Root, view: usize) -> usize {
+    if let Some(stride) = root.buffer_views[view].byte_stride {
+        return stride as usize;
+    }
+    root.accessors
+        .iter()
+        .find(|accessor| accessor.buffer_view.map(|index| index.value()) == Some(view))
+        .and_then(|accessor| match (accessor.component_type, accessor.type_) {
+            (Checked::Valid(component_type), Checked::Valid(type_)) => {
+                Some(component_type.0.size() * type_.multiplicity())
+            }
+            _ => None,
+        })
+        .unwrap_or(1)
+}
+
+/// A buffer view compressed with `EXT_meshopt_compression`.
+struct CompressedView {
+    view: usize,
+    /// The location of the compressed data in the first buffer.
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: usize,
+    count: usize,
+    mode: meshopt::Mode,
+}
+
+/// The `EXT_meshopt_compression` objects of the json, which `json::Root` can't hold.
+#[derive(Default)]
+struct MeshoptExtensions {
+    views: Vec<CompressedView>,
+    /// The buffer that only receives decompressed data, if any.
+    fallback_buffer: Option<usize>,
+}
+
+/// Serializes the json with the given extension objects.
+fn serialize_json(
+    root: &json::Root,
+    extensions: &MeshoptExtensions,
+    pretty: bool,
+) -> Result<String> {
+    let serialize = |value: &json::Value| {
+        if pretty {
+            json::serialize::to_string_pretty(value)
+        } else {
+            json::serialize::to_string(value)
+        }
+    };
+    if extensions.views.is_empty() && extensions.fallback_buffer.is_none() {
+        return Ok(if pretty {
+            json::serialize::to_string_pretty(root)?
+        } else {
+            json::serialize::to_string(root)?
+        });
+    }
+
+    let mut value = json::serialize::to_value(root)?;
+    for view in &extensions.views {
+        let extension = &mut value["bufferViews"][view.view]["extensions"][meshopt::EXTENSION_NAME];
+        extension["buffer"] = 0.into();
+        extension["byteOffset"] = view.byte_offset.into();
+        extension["byteLength"] = view.byte_length.into();
+        extension["byteStride"] = view.byte_stride.into();
+        extension["count"] = view.count.into();
+        extension["mode"] = match view.mode {
+            meshopt::Mode::Attributes => "ATTRIBUTES",
+            meshopt::Mode::Triangles => "TRIANGLES",
+            meshopt::Mode::Indices => "INDICES",
+        }
+        .into();
+    }
+    if let Some(buffer) = extensions.fallback_buffer {
+        value["buffers"][buffer]["extensions"][meshopt::EXTENSION_NAME]["fallback"] = true.into();
+    }
+
+    Ok(serialize(&value)?)
+}
+
 fn insert_animations(
     root: &mut json::Root,
     buffer: &mut Vec<u8>,
     animations: &[Animation],
-    skeleton_len: usize,
-    skeleton_index: usize,
+    skeleton: &[Joint],
+    nodes: AnimationNodes,
+    exporter: &GltfExporter,
+    warnings: &mut Vec<String>,
 ) -> Result<()> {
     for animation in animations {
-        let mut gltf_animation = json::Animation {
-            name: Some(animation.name.clone()),
-            samplers: Vec::new(),
-            channels: Vec::new(),
-            extensions: None,
-            extras: Default::default(),
+        let mut channels = AnimationChannels {
+            animation,
+            gltf_animation: json::Animation {
+                name: Some(animation.name.clone()),
+                samplers: Vec::new(),
+                channels: Vec::new(),
+                extensions: None,
+                extras: Default::default(),
+            },
+            time_accessors: HashMap::new(),
+            interpolation: exporter.interpolation,
+            // Keyframe tangents can't be stored as normalized integers because they may exceed 1.
+            quantize: exporter.quantize && exporter.interpolation == GltfInterpolation::Linear,
+            keyframe_reduction: exporter.keyframe_reduction,
         };
 
-        let time_accessor = insert_time_bytes(root, buffer, animation)?;
+        let root_translations: Vec<_> = animation
+            .frames
+            .iter()
+            .map(|frame| frame.translation.into())
+            .collect();
+        channels.insert_vectors(
+            root,
+            buffer,
+            &root_translations,
+            nodes.skeleton,
+            gltf::animation::Property::Translation,
+        )?;
+        if let Some(node) = nodes.root_motion {
+            let root_motions: Vec<_> = animation
+                .frames
+                .iter()
+                .map(|frame| frame.root_motion.into())
+                .collect();
+            channels.insert_vectors(
+                root,
+                buffer,
+                &root_motions,
+                node,
+                gltf::animation::Property::Translation,
+            )?;
+        }
+        if let Some(node) = nodes.frame_options {
+            // The options are stored in the X coordinate of a marker track, which survives
+            // editors that discard custom animation data.
+            let options: Vec<_> = animation
+                .frames
+                .iter()
+                .map(|frame| Vec3::new(frame.option as f32, 0., 0.))
+                .collect();
+            channels.insert_steps(root, buffer, &options, node)?;
+        }
+
+        let mut sheared_frames = BTreeSet::new();
+        let mut scaled_frames = BTreeSet::new();
+        for (index, (transforms, joint)) in animation.joints().iter().zip(skeleton).enumerate() {
+            let mut translations = Vec::new();
+            let mut rotations = Vec::new();
+            let mut scales = Vec::new();
+            for (frame, matrix) in transforms.iter().enumerate() {
+                let decomposition = keyframes::decompose(matrix);
+                // The translation of the joint matrix is an offset from the bind pose.
+                translations.push(Vec3::from(joint.translation) + decomposition.translation);
+                rotations.push(decomposition.rotation);
+                scales.push(decomposition.scale);
+
+                if decomposition.shear > MATRIX_TOLERANCE {
+                    sheared_frames.insert(frame);
+                }
+                if !decomposition.scale.abs_diff_eq(Vec3::ONE, MATRIX_TOLERANCE) {
+                    scaled_frames.insert(frame);
+                }
+            }
+
+            // The index of the joint corresponds to the index of the joint node because
+            // joints are the first things to be inserted into the scene hierarchy.
+            channels.insert_rotations(root, buffer, rotations, index)?;
+
+            // Translations and scales are only exported for the joints that make use of them.
+            let is_translated = transforms
+                .iter()
+                .any(|matrix| !matrix.w_axis.truncate().abs_diff_eq(Vec3::ZERO, EPSILON));
+            if is_translated {
+                channels.insert_vectors(
+                    root,
+                    buffer,
+                    &translations,
+                    index,
+                    gltf::animation::Property::Translation,
+                )?;
+            }
+            let is_scaled = scales
+                .iter()
+                .any(|scale| !scale.abs_diff_eq(Vec3::ONE, EPSILON));
+            if is_scaled {
+                channels.insert_vectors(
+                    root,
+                    buffer,
+                    &scales,
+                    index,
+                    gltf::animation::Property::Scale,
+                )?;
+            }
+        }
 
-        let translations_accessor = insert_translations_bytes(root, buffer, animation)?;
-        gltf_animation.samplers.push(json::animation::Sampler {
+        warnings.extend(distorted_frames_warning(
+            animation,
+            &sheared_frames,
+            "shear, which is discarded",
+        ));
+        warnings.extend(distorted_frames_warning(
+            animation,
+            &scaled_frames,
+            "scale, which is exported as a separate channel",
+        ));
+
+        root.animations.push(channels.gltf_animation);
+    }
+
+    Ok(())
+}
+
+/// Returns a warning that lists the frames of the animation whose joint matrices are not pure
+/// rotations, if there are any.
+fn distorted_frames_warning(
+    animation: &Animation,
+    frames: &BTreeSet<usize>,
+    distortion: &str,
+) -> Option<String> {
+    const MAX_LISTED_FRAMES: usize = 10;
+
+    if frames.is_empty() {
+        return None;
+    }
+
+    let mut list: Vec<_> = frames
+        .iter()
+        .take(MAX_LISTED_FRAMES)
+        .map(usize::to_string)
+        .collect();
+    if frames.len() > MAX_LISTED_FRAMES {
+        list.push(String::from("..."));
+    }
+    Some(format!(
+        "the joint matrices of animation \"{}\" contain {} in {} frame(s): {}.",
+        animation.name,
+        distortion,
+        frames.len(),
+        list.join(", ")
+    ))
+}
+
+/// The nodes animated by [`insert_animations`].
+#[derive(Debug, Clone, Copy)]
+struct AnimationNodes {
+    /// The node that moves the whole skeleton.
+    skeleton: usize,
+    /// The node animated with [`Keyframe::root_motion`], if any animation uses it.
+    ///
+    /// [`Keyframe::root_motion`]: crate::scene::Keyframe::root_motion
+    root_motion: Option<usize>,
+    /// The node animated with [`Keyframe::option`], if any animation uses it.
+    ///
+    /// [`Keyframe::option`]: crate::scene::Keyframe::option
+    frame_options: Option<usize>,
+}
+
+/// Inserts the samplers and channels of an animation.
+struct AnimationChannels<'a> {
+    animation: &'a Animation,
+    gltf_animation: json::Animation,
+    /// The time accessors that have already been inserted, indexed by their frames. Channels
+    /// with the same frames share the same accessor.
+    time_accessors: HashMap<Vec<usize>, usize>,
+    interpolation: GltfInterpolation,
+    quantize: bool,
+    keyframe_reduction: Option<KeyframeReduction>,
+}
+
+impl AnimationChannels<'_> {
+    /// Inserts a channel that animates the translation or scale of a node.
+    fn insert_vectors(
+        &mut self,
+        root: &mut json::Root,
+        buffer: &mut Vec<u8>,
+        vectors: &[Vec3],
+        node: usize,
+        property: gltf::animation::Property,
+    ) -> Result<()> {
+        let tangents = self.is_cubic().then(|| keyframes::vector_tangents(vectors));
+        let frames = match self.keyframe_reduction {
+            Some(reduction) => {
+                keyframes::reduce_vectors(vectors, tangents.as_deref(), reduction.max_distance)
+            }
+            None => (0..vectors.len()).collect(),
+        };
+
+        let time_accessor = self.insert_times(root, buffer, &frames)?;
+        let values = self.sampler_values(vectors, tangents.as_deref(), &frames);
+        let output_accessor = insert_vec3_bytes(root, buffer, &values)?;
+        self.push_channel(
+            time_accessor,
+            output_accessor,
+            node,
+            property,
+            self.sampler_interpolation(),
+        );
+        Ok(())
+    }
+
+    /// Inserts a channel that animates the rotation of a node.
+    fn insert_rotations(
+        &mut self,
+        root: &mut json::Root,
+        buffer: &mut Vec<u8>,
+        mut rotations: Vec<Quat>,
+        node: usize,
+    ) -> Result<()> {
+        keyframes::align_hemispheres(&mut rotations);
+        let tangents = self
+            .is_cubic()
+            .then(|| keyframes::rotation_tangents(&rotations));
+        let frames = match self.keyframe_reduction {
+            Some(reduction) => {
+                keyframes::reduce_rotations(&rotations, tangents.as_deref(), reduction.max_angle)
+            }
+            None => (0..rotations.len()).collect(),
+        };
+
+        let time_accessor = self.insert_times(root, buffer, &frames)?;
+        let values = self.sampler_values(&rotations, tangents.as_deref(), &frames);
+        let output_accessor = insert_rotations_bytes(root, buffer, &values, self.quantize)?;
+        self.push_channel(
+            time_accessor,
+            output_accessor,
+            node,
+            gltf::animation::Property::Rotation,
+            self.sampler_interpolation(),
+        );
+        Ok(())
+    }
+
+    /// Inserts a channel that animates the translation of a node with step interpolation. Only
+    /// the frames whose value differs from the previous frame are kept.
+    fn insert_steps(
+        &mut self,
+        root: &mut json::Root,
+        buffer: &mut Vec<u8>,
+        values: &[Vec3],
+        node: usize,
+    ) -> Result<()> {
+        let frames: Vec<_> = (0..values.len())
+            .filter(|&frame| frame == 0 || values[frame] != values[frame - 1])
+            .collect();
+
+        let time_accessor = self.insert_times(root, buffer, &frames)?;
+        let values: Vec<_> = frames.iter().map(|&frame| values[frame]).collect();
+        let output_accessor = insert_vec3_bytes(root, buffer, &values)?;
+        self.push_channel(
+            time_accessor,
+            output_accessor,
+            node,
+            gltf::animation::Property::Translation,
+            gltf::animation::Interpolation::Step,
+        );
+        Ok(())
+    }
+
+    fn is_cubic(&self) -> bool {
+        self.interpolation == GltfInterpolation::CubicSpline
+    }
+
+    fn sampler_interpolation(&self) -> gltf::animation::Interpolation {
+        match self.interpolation {
+            GltfInterpolation::Linear => gltf::animation::Interpolation::Linear,
+            GltfInterpolation::CubicSpline => gltf::animation::Interpolation::CubicSpline,
+        }
+    }
+
+    /// Inserts the times of the given frames, unless a channel with the same frames has already
+    /// inserted them.
+    fn insert_times(
+        &mut self,
+        root: &mut json::Root,
+        buffer: &mut Vec<u8>,
+        frames: &[usize],
+    ) -> Result<usize> {
+        if let Some(&accessor) = self.time_accessors.get(frames) {
+            return Ok(accessor);
+        }
+
+        let times: Vec<_> = frames
+            .iter()
+            .map(|&frame| self.animation.time(frame))
+            .collect();
+        let accessor = insert_time_bytes(root, buffer, &times)?;
+        self.time_accessors.insert(frames.to_vec(), accessor);
+        Ok(accessor)
+    }
+
+    /// Returns the output values of a sampler for the given frames. Cubic spline samplers store
+    /// an in-tangent, the value and an out-tangent per keyframe, with the tangents per second.
+    fn sampler_values<T>(&self, values: &[T], tangents: Option<&[T]>, frames: &[usize]) -> Vec<T>
+    where
+        T: Copy + Mul<f32, Output = T>,
+    {
+        match tangents {
+            Some(tangents) => frames
+                .iter()
+                .flat_map(|&frame| {
+                    let tangent = tangents[frame] * self.animation.sampling_rate;
+                    [tangent, values[frame], tangent]
+                })
+                .collect(),
+            None => frames.iter().map(|&frame| values[frame]).collect(),
+        }
+    }
+
+    /// Inserts a sampler and a channel that animates the given property of a node.
+    fn push_channel(
+        &mut self,
+        time_accessor: usize,
+        output_accessor: usize,
+        node: usize,
+        property: gltf::animation::Property,
+        interpolation: gltf::animation::Interpolation,
+    ) {
+        self.gltf_animation.samplers.push(json::animation::Sampler {
             input: json::Index::new(time_accessor as u32),
-            output: json::Index::new(translations_accessor as u32),
-            // For the sake of simplicity, we use linear interpolation. In reality,
-            // Grand Chase uses bezier curves with unknown in-tangent and out-tangent values.
-            interpolation: Checked::Valid(gltf::animation::Interpolation::Linear),
+            output: json::Index::new(output_accessor as u32),
+            interpolation: Checked::Valid(interpolation),
             extensions: None,
             extras: Default::default(),
         });
-        gltf_animation.channels.push(json::animation::Channel {
-            sampler: json::Index::new(gltf_animation.channels.len() as u32),
+        self.gltf_animation.channels.push(json::animation::Channel {
+            sampler: json::Index::new(self.gltf_animation.samplers.len() as u32 - 1),
             target: json::animation::Target {
-                node: json::Index::new(skeleton_index as u32),
-                path: Checked::Valid(gltf::animation::Property::Translation),
+                node: json::Index::new(node as u32),
+                path: Checked::Valid(property),
                 extensions: None,
                 extras: Default::default(),
             },
             extensions: None,
             extras: Default::default(),
         });
+    }
+}
 
-        for (index, transforms) in animation.joints().iter().enumerate().take(skeleton_len) {
-            let rotations_accessor = insert_rotations_bytes(root, buffer, transforms)?;
-            gltf_animation.samplers.push(json::animation::Sampler {
-                input: json::Index::new(time_accessor as u32),
-                output: json::Index::new(rotations_accessor as u32),
-                interpolation: Checked::Valid(gltf::animation::Interpolation::Linear),
-                extensions: None,
-                extras: Default::default(),
-            });
-            gltf_animation.channels.push(json::animation::Channel {
-                sampler: json::Index::new(gltf_animation.channels.len() as u32),
-                target: json::animation::Target {
-                    // The index of the loop corresponds to the index of the joint node
-                    // because joints are the first things to be inserted into the scene hierarchy.
-                    node: json::Index::new(index as u32),
-                    path: Checked::Valid(gltf::animation::Property::Rotation),
-                    extensions: None,
-                    extras: Default::default(),
-                },
-                extensions: None,
-                extras: Default::default(),
-            });
+/// Maps the vertex positions of the scene to 16-bit integers on a uniform grid that spans every
+/// mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PositionQuantization {
+    offset: Vec3,
+    scale: f32,
+}
 
-            // TODO: translations of individual joints are not currently supported for exporting.
+impl PositionQuantization {
+    fn new(meshes: &[Mesh]) -> Self {
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for vertex in meshes.iter().flat_map(|mesh| &mesh.vertices) {
+            min = min.min(vertex.position.into());
+            max = max.max(vertex.position.into());
         }
 
-        root.animations.push(gltf_animation);
+        let extent = (max - min).max_element();
+        if extent < 0. {
+            // There are no vertices.
+            return Self {
+                offset: Vec3::ZERO,
+                scale: 1.,
+            };
+        }
+        Self {
+            offset: min,
+            scale: if extent > 0. {
+                extent / u16::MAX as f32
+            } else {
+                1.
+            },
+        }
     }
 
-    Ok(())
+    fn quantize(&self, position: Vec3) -> [u16; 3] {
+        ((position - self.offset) / self.scale)
+            .round()
+            .to_array()
+            .map(|value| value as u16)
+    }
+
+    /// Returns the transform from the quantized positions to the original ones.
+    fn dequantization_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(Vec3::splat(self.scale), Quat::IDENTITY, self.offset)
+    }
 }
 
 fn insert_positions_bytes(
     root: &mut json::Root,
     buffer: &mut Vec<u8>,
     mesh: &Mesh,
+    quantization: Option<&PositionQuantization>,
 ) -> Result<usize> {
+    let positions: Vec<[f32; 3]> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| match quantization {
+            Some(quantization) => quantization.quantize(vertex.position.into()).map(f32::from),
+            None => vertex.position.into(),
+        })
+        .collect();
+    let bound = |select: fn(f32, f32) -> f32| -> [f32; 3] {
+        let mut bound = positions.first().copied().unwrap_or_default();
+        for position in &positions {
+            for (bound, &coordinate) in bound.iter_mut().zip(position) {
+                *bound = select(*bound, coordinate);
+            }
+        }
+        bound
+    };
+    let to_json = |bound: [f32; 3]| -> json::Value {
+        match quantization {
+            Some(_) => bound.map(|value| value as u16).to_vec().into(),
+            None => bound.to_vec().into(),
+        }
+    };
+
+    // Quantized positions are padded to keep each element 4-byte aligned.
+    let (component_type, stride) = match quantization {
+        Some(_) => (
+            json::accessor::ComponentType::U16,
+            mem::size_of::<[u16; 4]>(),
+        ),
+        None => (
+            json::accessor::ComponentType::F32,
+            mem::size_of::<[f32; 3]>(),
+        ),
+    };
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
         count: mesh.vertices.len() as u32,
         type_: Checked::Valid(json::accessor::Type::Vec3),
-        component_type: Checked::Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
-        min: Some(
-            vec![
-                mesh.vertices
-                    .iter()
-                    .map(|v| v.position.x)
-                    .min_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or_default(),
-                mesh.vertices
-                    .iter()
-                    .map(|v| v.position.y)
-                    .min_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or_default(),
-                mesh.vertices
-                    .iter()
-                    .map(|v| v.position.z)
-                    .min_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or_default(),
-            ]
-            .into(),
-        ),
-        max: Some(
-            vec![
-                mesh.vertices
-                    .iter()
-                    .map(|v| v.position.x)
-                    .max_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or_default(),
-                mesh.vertices
-                    .iter()
-                    .map(|v| v.position.y)
-                    .max_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or_default(),
-                mesh.vertices
-                    .iter()
-                    .map(|v| v.position.z)
-                    .max_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or_default(),
-            ]
-            .into(),
-        ),
+        component_type: Checked::Valid(json::accessor::GenericComponentType(component_type)),
+        min: Some(to_json(bound(f32::min))),
+        max: Some(to_json(bound(f32::max))),
         name: None,
         normalized: false,
         sparse: None,
@@ -410,17 +1104,23 @@ fn insert_positions_bytes(
     let view = json::buffer::View {
         buffer: json::Index::new(root.buffers.len() as u32),
         byte_offset: Some(buffer.len() as u32),
-        byte_length: (mesh.vertices.len() * mem::size_of::<[f32; 3]>()) as u32,
-        byte_stride: None,
+        byte_length: (mesh.vertices.len() * stride) as u32,
+        byte_stride: quantization.map(|_| stride as u32),
         name: None,
         target: None,
         extensions: None,
         extras: Default::default(),
     };
 
-    for vertex in &mesh.vertices {
-        for &coordinate in vertex.position.as_ref() {
-            buffer.write_f32::<LE>(coordinate)?;
+    for position in &positions {
+        for &coordinate in position {
+            match quantization {
+                Some(_) => buffer.write_u16::<LE>(coordinate as u16)?,
+                None => buffer.write_f32::<LE>(coordinate)?,
+            }
+        }
+        if quantization.is_some() {
+            buffer.write_u16::<LE>(0)?;
         }
     }
 
@@ -430,19 +1130,31 @@ fn insert_positions_bytes(
     Ok(root.accessors.len() - 1)
 }
 
-fn insert_normals_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh) -> Result<usize> {
+fn insert_normals_bytes(
+    root: &mut json::Root,
+    buffer: &mut Vec<u8>,
+    mesh: &Mesh,
+    quantize: bool,
+) -> Result<usize> {
+    // Quantized normals are padded to keep each element 4-byte aligned.
+    let (component_type, stride) = if quantize {
+        (json::accessor::ComponentType::I8, mem::size_of::<[i8; 4]>())
+    } else {
+        (
+            json::accessor::ComponentType::F32,
+            mem::size_of::<[f32; 3]>(),
+        )
+    };
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
         count: mesh.vertices.len() as u32,
         type_: Checked::Valid(json::accessor::Type::Vec3),
-        component_type: Checked::Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
+        component_type: Checked::Valid(json::accessor::GenericComponentType(component_type)),
         min: None,
         max: None,
         name: None,
-        normalized: false,
+        normalized: quantize,
         sparse: None,
         extensions: None,
         extras: Default::default(),
@@ -452,8 +1164,8 @@ fn insert_normals_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh
     let view = json::buffer::View {
         buffer: json::Index::new(root.buffers.len() as u32),
         byte_offset: Some(buffer.len() as u32),
-        byte_length: (mesh.vertices.len() * mem::size_of::<[f32; 3]>()) as u32,
-        byte_stride: None,
+        byte_length: (mesh.vertices.len() * stride) as u32,
+        byte_stride: quantize.then_some(stride as u32),
         name: None,
         target: None,
         extensions: None,
@@ -462,7 +1174,14 @@ fn insert_normals_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh
 
     for vertex in &mesh.vertices {
         for &coordinate in vertex.normal.normalize_or_zero().as_ref() {
-            buffer.write_f32::<LE>(coordinate)?;
+            if quantize {
+                buffer.write_i8(quantize_normalized(coordinate, i8::MAX as f32) as i8)?;
+            } else {
+                buffer.write_f32::<LE>(coordinate)?;
+            }
+        }
+        if quantize {
+            buffer.write_i8(0)?;
         }
     }
 
@@ -472,19 +1191,39 @@ fn insert_normals_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh
     Ok(root.accessors.len() - 1)
 }
 
-fn insert_uv_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh) -> Result<usize> {
+fn insert_uv_bytes(
+    root: &mut json::Root,
+    buffer: &mut Vec<u8>,
+    mesh: &Mesh,
+    quantize: bool,
+) -> Result<usize> {
+    // Texture coordinates outside of the texture, which repeat it, can't be normalized.
+    let quantize = quantize
+        && mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.uv.cmpge(Vec2::ZERO).all() && vertex.uv.cmple(Vec2::ONE).all());
+    let (component_type, stride) = if quantize {
+        (
+            json::accessor::ComponentType::U16,
+            mem::size_of::<[u16; 2]>(),
+        )
+    } else {
+        (
+            json::accessor::ComponentType::F32,
+            mem::size_of::<[f32; 2]>(),
+        )
+    };
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
         count: mesh.vertices.len() as u32,
         type_: Checked::Valid(json::accessor::Type::Vec2),
-        component_type: Checked::Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
+        component_type: Checked::Valid(json::accessor::GenericComponentType(component_type)),
         min: None,
         max: None,
         name: None,
-        normalized: false,
+        normalized: quantize,
         sparse: None,
         extensions: None,
         extras: Default::default(),
@@ -494,7 +1233,7 @@ fn insert_uv_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh) ->
     let view = json::buffer::View {
         buffer: json::Index::new(root.buffers.len() as u32),
         byte_offset: Some(buffer.len() as u32),
-        byte_length: (mesh.vertices.len() * mem::size_of::<[f32; 2]>()) as u32,
+        byte_length: (mesh.vertices.len() * stride) as u32,
         byte_stride: None,
         name: None,
         target: None,
@@ -504,7 +1243,11 @@ fn insert_uv_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh) ->
 
     for vertex in &mesh.vertices {
         for &coordinate in vertex.uv.as_ref() {
-            buffer.write_f32::<LE>(coordinate)?;
+            if quantize {
+                buffer.write_u16::<LE>(quantize_normalized(coordinate, u16::MAX as f32) as u16)?;
+            } else {
+                buffer.write_f32::<LE>(coordinate)?;
+            }
         }
     }
 
@@ -514,7 +1257,12 @@ fn insert_uv_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh) ->
     Ok(root.accessors.len() - 1)
 }
 
-fn insert_joints_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh) -> Result<usize> {
+fn insert_joints_bytes(
+    root: &mut json::Root,
+    buffer: &mut Vec<u8>,
+    mesh: &Mesh,
+    unskinned_joint: Option<usize>,
+) -> Result<usize> {
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
@@ -545,7 +1293,8 @@ fn insert_joints_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh)
     };
 
     for vertex in &mesh.vertices {
-        buffer.extend_from_slice(&[vertex.joint.unwrap_or_default() as u8, 0, 0, 0]);
+        let joint = vertex.joint.or(unskinned_joint).unwrap_or_default();
+        buffer.extend_from_slice(&[joint as u8, 0, 0, 0]);
     }
 
     root.accessors.push(accessor);
@@ -554,19 +1303,31 @@ fn insert_joints_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh)
     Ok(root.accessors.len() - 1)
 }
 
-fn insert_weights_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh) -> Result<usize> {
+fn insert_weights_bytes(
+    root: &mut json::Root,
+    buffer: &mut Vec<u8>,
+    mesh: &Mesh,
+    quantize: bool,
+    bind_unskinned: bool,
+) -> Result<usize> {
+    let (component_type, stride) = if quantize {
+        (json::accessor::ComponentType::U8, mem::size_of::<[u8; 4]>())
+    } else {
+        (
+            json::accessor::ComponentType::F32,
+            mem::size_of::<[f32; 4]>(),
+        )
+    };
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
         count: mesh.vertices.len() as u32,
         type_: Checked::Valid(json::accessor::Type::Vec4),
-        component_type: Checked::Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
+        component_type: Checked::Valid(json::accessor::GenericComponentType(component_type)),
         min: None,
         max: None,
         name: None,
-        normalized: false,
+        normalized: quantize,
         sparse: None,
         extensions: None,
         extras: Default::default(),
@@ -576,7 +1337,7 @@ fn insert_weights_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh
     let view = json::buffer::View {
         buffer: json::Index::new(root.buffers.len() as u32),
         byte_offset: Some(buffer.len() as u32),
-        byte_length: (mesh.vertices.len() * mem::size_of::<[f32; 4]>()) as u32,
+        byte_length: (mesh.vertices.len() * stride) as u32,
         byte_stride: None,
         name: None,
         target: None,
@@ -587,10 +1348,15 @@ fn insert_weights_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh
     for vertex in &mesh.vertices {
         let weight = match vertex.joint {
             Some(_) => 1.,
+            None if bind_unskinned => 1.,
             None => 0.,
         };
         for coordinate in [weight, 0., 0., 0.] {
-            buffer.write_f32::<LE>(coordinate)?;
+            if quantize {
+                buffer.write_u8(quantize_normalized(coordinate, u8::MAX as f32) as u8)?;
+            } else {
+                buffer.write_f32::<LE>(coordinate)?;
+            }
         }
     }
 
@@ -640,10 +1406,13 @@ fn insert_indices_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, mesh: &Mesh
     Ok(root.accessors.len() - 1)
 }
 
+/// Inserts the inverse bind matrices of the joints, which also apply the given transform to the
+/// vertex positions.
 fn insert_inverse_bind_bytes(
     root: &mut json::Root,
     buffer: &mut Vec<u8>,
     scene: &Scene,
+    vertex_transform: Mat4,
 ) -> Result<usize> {
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
@@ -675,13 +1444,13 @@ fn insert_inverse_bind_bytes(
     };
 
     for (index, _) in scene.skeleton.iter().enumerate() {
-        let matrix = scene.joint_world_transform(index).inverse();
+        let matrix = scene.joint_world_transform(index).inverse() * vertex_transform;
         for value in matrix.to_cols_array() {
             buffer.write_f32::<LE>(value)?;
         }
     }
     // Write root inverse bind matrix.
-    let matrix = Mat4::IDENTITY;
+    let matrix = vertex_transform;
     for value in matrix.to_cols_array() {
         buffer.write_f32::<LE>(value)?;
     }
@@ -692,22 +1461,11 @@ fn insert_inverse_bind_bytes(
     Ok(root.accessors.len() - 1)
 }
 
-fn insert_time_bytes(
-    root: &mut json::Root,
-    buffer: &mut Vec<u8>,
-    animation: &Animation,
-) -> Result<usize> {
-    let times: Vec<_> = animation
-        .frames
-        .iter()
-        .enumerate()
-        .map(|(i, _)| i as f32 * (1. / animation.sampling_rate() as f32))
-        .collect();
-
+fn insert_time_bytes(root: &mut json::Root, buffer: &mut Vec<u8>, times: &[f32]) -> Result<usize> {
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
-        count: animation.frames.len() as u32,
+        count: times.len() as u32,
         type_: Checked::Valid(json::accessor::Type::Scalar),
         component_type: Checked::Valid(json::accessor::GenericComponentType(
             json::accessor::ComponentType::F32,
@@ -724,7 +1482,7 @@ fn insert_time_bytes(
         max: Some(
             [times
                 .iter()
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
+                .max_by(|a, b| a.partial_cmp(b).unwrap())
                 .copied()
                 .unwrap_or_default()]
             .as_ref()
@@ -741,7 +1499,7 @@ fn insert_time_bytes(
     let view = json::buffer::View {
         buffer: json::Index::new(root.buffers.len() as u32),
         byte_offset: Some(buffer.len() as u32),
-        byte_length: (animation.frames.len() * mem::size_of::<f32>()) as u32,
+        byte_length: mem::size_of_val(times) as u32,
         byte_stride: None,
         name: None,
         target: None,
@@ -749,7 +1507,7 @@ fn insert_time_bytes(
         extras: Default::default(),
     };
 
-    for &time in &times {
+    for &time in times {
         buffer.write_f32::<LE>(time)?;
     }
 
@@ -759,15 +1517,15 @@ fn insert_time_bytes(
     Ok(root.accessors.len() - 1)
 }
 
-fn insert_translations_bytes(
+fn insert_vec3_bytes(
     root: &mut json::Root,
     buffer: &mut Vec<u8>,
-    animation: &Animation,
+    values: &[Vec3],
 ) -> Result<usize> {
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
-        count: animation.frames.len() as u32,
+        count: values.len() as u32,
         type_: Checked::Valid(json::accessor::Type::Vec3),
         component_type: Checked::Valid(json::accessor::GenericComponentType(
             json::accessor::ComponentType::F32,
@@ -785,7 +1543,7 @@ fn insert_translations_bytes(
     let view = json::buffer::View {
         buffer: json::Index::new(root.buffers.len() as u32),
         byte_offset: Some(buffer.len() as u32),
-        byte_length: (animation.frames.len() * mem::size_of::<[f32; 3]>()) as u32,
+        byte_length: (values.len() * mem::size_of::<[f32; 3]>()) as u32,
         byte_stride: None,
         name: None,
         target: None,
@@ -793,8 +1551,8 @@ fn insert_translations_bytes(
         extras: Default::default(),
     };
 
-    for frame in &animation.frames {
-        for &coordinate in frame.translation.as_ref() {
+    for value in values {
+        for &coordinate in value.as_ref() {
             buffer.write_f32::<LE>(coordinate)?;
         }
     }
@@ -808,20 +1566,30 @@ fn insert_translations_bytes(
 fn insert_rotations_bytes(
     root: &mut json::Root,
     buffer: &mut Vec<u8>,
-    rotations: &[&Mat4],
+    rotations: &[Quat],
+    quantize: bool,
 ) -> Result<usize> {
+    let (component_type, stride) = if quantize {
+        (
+            json::accessor::ComponentType::I16,
+            mem::size_of::<[i16; 4]>(),
+        )
+    } else {
+        (
+            json::accessor::ComponentType::F32,
+            mem::size_of::<[f32; 4]>(),
+        )
+    };
     let accessor = json::Accessor {
         buffer_view: Some(json::Index::new(root.buffer_views.len() as u32)),
         byte_offset: 0,
         count: rotations.len() as u32,
         type_: Checked::Valid(json::accessor::Type::Vec4),
-        component_type: Checked::Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
+        component_type: Checked::Valid(json::accessor::GenericComponentType(component_type)),
         min: None,
         max: None,
         name: None,
-        normalized: false,
+        normalized: quantize,
         sparse: None,
         extensions: None,
         extras: Default::default(),
@@ -831,7 +1599,7 @@ fn insert_rotations_bytes(
     let view = json::buffer::View {
         buffer: json::Index::new(root.buffers.len() as u32),
         byte_offset: Some(buffer.len() as u32),
-        byte_length: (rotations.len() * mem::size_of::<[f32; 4]>()) as u32,
+        byte_length: (rotations.len() * stride) as u32,
         byte_stride: None,
         name: None,
         target: None,
@@ -839,10 +1607,13 @@ fn insert_rotations_bytes(
         extras: Default::default(),
     };
 
-    for matrix in rotations {
-        let (_, rotation, _) = matrix.to_scale_rotation_translation();
+    for rotation in rotations {
         for &value in rotation.as_ref() {
-            buffer.write_f32::<LE>(value)?;
+            if quantize {
+                buffer.write_i16::<LE>(quantize_normalized(value, i16::MAX as f32) as i16)?;
+            } else {
+                buffer.write_f32::<LE>(value)?;
+            }
         }
     }
 
@@ -854,7 +1625,13 @@ fn insert_rotations_bytes(
 
 /// Adds zeros to the buffer until it is n-byte aligned.
 fn align_to(buffer: &mut Vec<u8>, n: usize) {
-    buffer.append(&mut vec![0; buffer.len() % n]);
+    buffer.resize(buffer.len().next_multiple_of(n), 0);
+}
+
+/// Converts a value in the `[-1, 1]` range, or `[0, 1]` for unsigned types, to a normalized
+/// integer whose largest value is `max`.
+fn quantize_normalized(value: f32, max: f32) -> f32 {
+    (value.clamp(-1., 1.) * max).round()
 }
 
 #[cfg(test)]
@@ -863,6 +1640,11 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
+    use crate::{
+        conversion::Importer,
+        formats::GltfImporter,
+        scene::{Keyframe, Vertex},
+    };
 
     #[test]
     fn scene_nodes() {
@@ -892,7 +1674,7 @@ mod tests {
             vertices: Vec::new(),
             indices: Vec::new(),
         }];
-        let skeleton_node = insert_scene(&mut root, &skeleton, &meshes);
+        let skeleton_node = insert_scene(&mut root, &skeleton, &meshes, None);
 
         assert_eq!(0, root.scene.unwrap().value());
         assert_eq!(
@@ -907,4 +1689,468 @@ mod tests {
         assert_eq!(Some(String::from("mesh_goblin")), root.nodes[4].name);
         assert_eq!(Some([2., 2., 2.]), root.nodes[1].translation);
     }
+
+    #[test]
+    fn separate_files() {
+        let scene = Scene {
+            meshes: vec![Mesh {
+                name: String::from("goblin"),
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let exporter = GltfExporter {
+            format: GltfFormat::Separate,
+            ..Default::default()
+        };
+
+        let (assets, _) = exporter.export(&scene).unwrap();
+        assert_eq!(2, assets.len());
+        assert_eq!("goblin.gltf", assets[0].path().to_str().unwrap());
+        assert_eq!("goblin.bin", assets[1].path().to_str().unwrap());
+
+        let root = json::Root::from_slice(&assets[0].bytes).unwrap();
+        assert_eq!(Some(String::from("goblin.bin")), root.buffers[0].uri);
+        assert_eq!(assets[1].bytes.len(), root.buffers[0].byte_length as usize);
+
+        // Names that aren't valid in URIs are percent-encoded, and decoded when importing.
+        let skinned_scene = Scene {
+            meshes: vec![Mesh {
+                name: String::from("red goblin #1"),
+                vertices: vec![
+                    Vertex {
+                        position: Vec3A::ZERO,
+                        normal: Vec3A::Y,
+                        uv: Vec2::ZERO,
+                        joint: Some(0),
+                    };
+                    3
+                ],
+                indices: vec![0, 1, 2],
+            }],
+            skeleton: vec![Joint::default()],
+            ..Default::default()
+        };
+        let (assets, _) = exporter.export(&skinned_scene).unwrap();
+        let root = json::Root::from_slice(&assets[0].bytes).unwrap();
+        assert_eq!(
+            Some(String::from("red%20goblin%20%231.bin")),
+            root.buffers[0].uri
+        );
+
+        let dir = std::env::temp_dir().join("chaseconv_separate_files");
+        std::fs::create_dir_all(&dir).unwrap();
+        for asset in &assets {
+            std::fs::write(dir.join(asset.path()), &asset.bytes).unwrap();
+        }
+        let gltf_path = dir.join(assets[0].path());
+        let asset = Asset::from_path(gltf_path.to_str().unwrap()).unwrap();
+        let mut actual = Scene::default();
+        let result = GltfImporter::default().import(&asset, &mut actual);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        assert_eq!(3, actual.meshes[0].vertices.len());
+    }
+
+    #[test]
+    fn compressed_round_trip() {
+        let vertex = |position: Vec3A, uv: Vec2, joint: usize| Vertex {
+            position,
+            normal: Vec3A::new(0., 0.6, 0.8),
+            uv,
+            joint: Some(joint),
+        };
+        let rotation = Quat::from_rotation_y(0.3);
+        let scene = Scene {
+            meshes: vec![Mesh {
+                name: String::from("goblin"),
+                vertices: vec![
+                    vertex(Vec3A::new(-1., 0., 0.), Vec2::ZERO, 0),
+                    vertex(Vec3A::new(1., 0.5, 0.), Vec2::new(1., 0.), 0),
+                    vertex(Vec3A::new(0., 3., 0.2), Vec2::new(0.25, 1.), 1),
+                ],
+                indices: vec![0, 1, 2],
+            }],
+            skeleton: vec![
+                Joint {
+                    translation: Vec3A::new(0., 1., 0.),
+                    children: vec![1],
+                    ..Default::default()
+                },
+                Joint {
+                    translation: Vec3A::new(0., 2., 0.),
+                    parent: Some(0),
+                    ..Default::default()
+                },
+            ],
+            animations: vec![Animation {
+                name: String::from("nod"),
+                sampling_rate: 55.,
+                frames: vec![
+                    Keyframe {
+                        translation: Vec3A::ZERO,
+                        root_motion: Vec3A::ZERO,
+                        option: 0,
+                        transforms: vec![Mat4::IDENTITY, Mat4::IDENTITY],
+                    },
+                    Keyframe {
+                        translation: Vec3A::ZERO,
+                        root_motion: Vec3A::ZERO,
+                        option: 0,
+                        transforms: vec![Mat4::IDENTITY, Mat4::from_quat(rotation)],
+                    },
+                ],
+            }],
+        };
+
+        for compression in [
+            GltfCompression::None,
+            GltfCompression::Meshopt,
+            GltfCompression::MeshoptWithFallback,
+        ] {
+            let exporter = GltfExporter {
+                quantize: true,
+                compression,
+                ..Default::default()
+            };
+            let (assets, _) = exporter.export(&scene).unwrap();
+
+            let gltf = gltf::Gltf::from_slice(&assets[0].bytes).unwrap();
+            let required: Vec<_> = gltf.extensions_required().collect();
+            let buffers = gltf.buffers().count();
+            match compression {
+                GltfCompression::None => assert_eq!(vec![QUANTIZATION_EXTENSION_NAME], required),
+                GltfCompression::Meshopt => {
+                    assert_eq!(
+                        vec![QUANTIZATION_EXTENSION_NAME, meshopt::EXTENSION_NAME],
+                        required
+                    );
+                    assert_eq!(2, buffers);
+                }
+                GltfCompression::MeshoptWithFallback => {
+                    assert_eq!(vec![QUANTIZATION_EXTENSION_NAME], required);
+                    assert_eq!(1, buffers);
+                }
+            }
+
+            let mut actual = Scene::default();
+            GltfImporter::default()
+                .import(&assets[0], &mut actual)
+                .unwrap();
+
+            let mesh = &actual.meshes[0];
+            assert_eq!(scene.meshes[0].indices, mesh.indices);
+            for (expected, actual) in scene.meshes[0].vertices.iter().zip(&mesh.vertices) {
+                assert!(expected.position.abs_diff_eq(actual.position, 1e-4));
+                assert!(expected.normal.abs_diff_eq(actual.normal, 1e-2));
+                assert!(expected.uv.abs_diff_eq(actual.uv, 1e-4));
+                assert_eq!(expected.joint, actual.joint);
+            }
+            let transform = actual.animations[0].frames[1].transforms[1];
+            assert!(transform.abs_diff_eq(Mat4::from_quat(rotation), 1e-4));
+        }
+    }
+
+    #[test]
+    fn quantized_unskinned_vertices() {
+        let vertex = |position: Vec3A, joint: Option<usize>| Vertex {
+            position,
+            normal: Vec3A::Y,
+            uv: Vec2::ZERO,
+            joint,
+        };
+        let scene = Scene {
+            meshes: vec![Mesh {
+                name: String::from("sword"),
+                vertices: vec![
+                    vertex(Vec3A::new(-1., 0., 0.), Some(0)),
+                    vertex(Vec3A::new(1., 0.5, 0.), Some(0)),
+                    vertex(Vec3A::new(0., 3., 0.2), None),
+                ],
+                indices: vec![0, 1, 2],
+            }],
+            skeleton: vec![Joint {
+                translation: Vec3A::new(0., 1., 0.),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let exporter = GltfExporter {
+            quantize: true,
+            ..Default::default()
+        };
+        let (assets, _) = exporter.export(&scene).unwrap();
+
+        // The unskinned vertex is bound to the skeleton root, after the joints.
+        let gltf = gltf::Gltf::from_slice(&assets[0].bytes).unwrap();
+        let buffer = gltf.blob.as_deref().unwrap();
+        let primitive = gltf.meshes().next().unwrap().primitives().next().unwrap();
+        let reader = primitive.reader(|_| Some(buffer));
+        let joints: Vec<_> = reader.read_joints(0).unwrap().into_u16().collect();
+        let weights: Vec<_> = reader.read_weights(0).unwrap().into_f32().collect();
+        assert_eq!([1, 0, 0, 0], joints[2]);
+        assert_eq!([1., 0., 0., 0.], weights[2]);
+
+        let mut actual = Scene::default();
+        GltfImporter::default()
+            .import(&assets[0], &mut actual)
+            .unwrap();
+        for (expected, actual) in scene.meshes[0]
+            .vertices
+            .iter()
+            .zip(&actual.meshes[0].vertices)
+        {
+            assert!(expected.position.abs_diff_eq(actual.position, 1e-4));
+            assert_eq!(expected.joint, actual.joint);
+        }
+    }
+
+    #[test]
+    fn reduced_keyframes() {
+        let rotation = Quat::from_rotation_y(1.);
+        let frames: Vec<_> = (0..20)
+            .map(|i| Keyframe {
+                translation: Vec3A::new(0., 0., i as f32 * 0.1),
+                root_motion: Vec3A::ZERO,
+                option: 0,
+                transforms: vec![
+                    Mat4::IDENTITY,
+                    Mat4::from_quat(Quat::IDENTITY.slerp(rotation, (i.min(10) as f32) / 10.)),
+                ],
+            })
+            .collect();
+        let scene = Scene {
+            skeleton: vec![
+                Joint {
+                    children: vec![1],
+                    ..Default::default()
+                },
+                Joint {
+                    translation: Vec3A::new(0., 2., 0.),
+                    parent: Some(0),
+                    ..Default::default()
+                },
+            ],
+            animations: vec![Animation {
+                name: String::from("turn"),
+                sampling_rate: 55.,
+                frames,
+            }],
+            ..Default::default()
+        };
+
+        let exporter = GltfExporter {
+            keyframe_reduction: Some(KeyframeReduction::default()),
+            ..Default::default()
+        };
+        let (assets, _) = exporter.export(&scene).unwrap();
+
+        let gltf = gltf::Gltf::from_slice(&assets[0].bytes).unwrap();
+        let animation = gltf.animations().next().unwrap();
+        let counts: Vec<_> = animation
+            .samplers()
+            .map(|sampler| sampler.input().count())
+            .collect();
+        assert_eq!(vec![2, 2, 3], counts);
+
+        let mut actual = Scene::default();
+        GltfImporter::default()
+            .import(&assets[0], &mut actual)
+            .unwrap();
+
+        let animation = &actual.animations[0];
+        assert_eq!(55., animation.sampling_rate);
+        assert_eq!(scene.animations[0].frames.len(), animation.frames.len());
+        for (expected, actual) in scene.animations[0].frames.iter().zip(&animation.frames) {
+            assert!(expected.translation.abs_diff_eq(actual.translation, 1e-3));
+            for (expected, actual) in expected.transforms.iter().zip(&actual.transforms) {
+                assert!(expected.abs_diff_eq(*actual, 1e-3));
+            }
+        }
+    }
+
+    #[test]
+    fn cubic_spline_round_trip() {
+        let frames: Vec<_> = (0..30)
+            .map(|i| {
+                let angle = (i as f32 * 0.2).sin() * 2.;
+                Keyframe {
+                    translation: Vec3A::new(0., (i as f32 * 0.1).cos(), 0.),
+                    root_motion: Vec3A::ZERO,
+                    option: 0,
+                    transforms: vec![Mat4::IDENTITY, Mat4::from_rotation_x(angle)],
+                }
+            })
+            .collect();
+        let scene = Scene {
+            skeleton: vec![
+                Joint {
+                    children: vec![1],
+                    ..Default::default()
+                },
+                Joint {
+                    translation: Vec3A::new(0., 2., 0.),
+                    parent: Some(0),
+                    ..Default::default()
+                },
+            ],
+            animations: vec![Animation {
+                name: String::from("wave"),
+                sampling_rate: 55.,
+                frames,
+            }],
+            ..Default::default()
+        };
+
+        for keyframe_reduction in [None, Some(KeyframeReduction::default())] {
+            let exporter = GltfExporter {
+                quantize: true,
+                keyframe_reduction,
+                interpolation: GltfInterpolation::CubicSpline,
+                ..Default::default()
+            };
+            let (assets, _) = exporter.export(&scene).unwrap();
+
+            let gltf = gltf::Gltf::from_slice(&assets[0].bytes).unwrap();
+            for sampler in gltf.animations().next().unwrap().samplers() {
+                assert_eq!(
+                    gltf::animation::Interpolation::CubicSpline,
+                    sampler.interpolation()
+                );
+                assert_eq!(3 * sampler.input().count(), sampler.output().count());
+                assert_eq!(gltf::accessor::DataType::F32, sampler.output().data_type());
+            }
+
+            let mut actual = Scene::default();
+            GltfImporter::default()
+                .import(&assets[0], &mut actual)
+                .unwrap();
+
+            let animation = &actual.animations[0];
+            assert_eq!(scene.animations[0].frames.len(), animation.frames.len());
+            for (expected, actual) in scene.animations[0].frames.iter().zip(&animation.frames) {
+                assert!(expected.translation.abs_diff_eq(actual.translation, 1e-3));
+                for (expected, actual) in expected.transforms.iter().zip(&actual.transforms) {
+                    assert!(expected.abs_diff_eq(*actual, 2e-3));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn continuous_rotations() {
+        let mut skew = Mat4::from_rotation_x(0.5);
+        skew.y_axis += skew.z_axis * 0.1;
+        let frames: Vec<_> = (0..40)
+            .map(|i| Keyframe {
+                translation: Vec3A::ZERO,
+                root_motion: Vec3A::ZERO,
+                option: 0,
+                transforms: vec![if i == 20 {
+                    skew
+                } else {
+                    Mat4::from_rotation_x(i as f32 * 0.25)
+                }],
+            })
+            .collect();
+        let scene = Scene {
+            skeleton: vec![Joint::default()],
+            animations: vec![Animation {
+                name: String::from("spin"),
+                sampling_rate: 55.,
+                frames,
+            }],
+            ..Default::default()
+        };
+
+        let (assets, _) = GltfExporter::default().export(&scene).unwrap();
+
+        let (gltf, buffers, _) = gltf::import_slice(&assets[0].bytes).unwrap();
+        let channel = gltf.animations().next().unwrap().channels().nth(1).unwrap();
+        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+        let rotations: Vec<_> = match reader.read_outputs().unwrap() {
+            gltf::animation::util::ReadOutputs::Rotations(rotations) => {
+                rotations.into_f32().map(Quat::from_array).collect()
+            }
+            _ => panic!("the channel should animate rotations"),
+        };
+        assert_eq!(40, rotations.len());
+        assert!(rotations.iter().all(|rotation| rotation.is_normalized()));
+        assert!(rotations.windows(2).all(|pair| pair[0].dot(pair[1]) > 0.));
+    }
+
+    #[test]
+    fn separate_tracks_round_trip() {
+        let scene = Scene {
+            skeleton: vec![Joint::default()],
+            animations: vec![Animation {
+                name: String::from("dash"),
+                sampling_rate: 55.,
+                frames: (0..4)
+                    .map(|i| Keyframe {
+                        translation: Vec3A::new(0., i as f32, 0.),
+                        root_motion: Vec3A::new(i as f32 * 2., 0., -1.),
+                        option: [0, 5, 5, 0][i],
+                        transforms: vec![Mat4::IDENTITY],
+                    })
+                    .collect(),
+            }],
+            ..Default::default()
+        };
+
+        let (assets, _) = GltfExporter::default().export(&scene).unwrap();
+        let gltf = gltf::Gltf::from_slice(&assets[0].bytes).unwrap();
+        let names: Vec<_> = gltf
+            .scenes()
+            .next()
+            .unwrap()
+            .nodes()
+            .filter_map(|node| node.name())
+            .collect();
+        assert!(names.contains(&ROOT_MOTION_NODE_NAME));
+        assert!(names.contains(&FRAME_OPTIONS_NODE_NAME));
+        let option_keyframes = gltf
+            .animations()
+            .next()
+            .unwrap()
+            .samplers()
+            .find(|sampler| sampler.interpolation() == gltf::animation::Interpolation::Step)
+            .map(|sampler| sampler.input().count());
+        assert_eq!(Some(3), option_keyframes);
+
+        let mut actual = Scene::default();
+        GltfImporter::default()
+            .import(&assets[0], &mut actual)
+            .unwrap();
+        assert_eq!(scene.animations[0].frames, actual.animations[0].frames);
+    }
+
+    #[test]
+    fn distorted_frames_warnings() {
+        let scene = Scene {
+            skeleton: vec![Joint::default()],
+            animations: vec![Animation {
+                name: String::from("grow"),
+                sampling_rate: 55.,
+                frames: (0..3)
+                    .map(|i| Keyframe {
+                        transforms: vec![Mat4::from_scale(Vec3::splat(1. + i as f32))],
+                        ..Default::default()
+                    })
+                    .collect(),
+            }],
+            ..Default::default()
+        };
+
+        let (_, warnings) = GltfExporter::default().export(&scene).unwrap();
+        assert_eq!(
+            vec![String::from(
+                "the joint matrices of animation \"grow\" contain scale, which is exported as a \
+                separate channel in 2 frame(s): 1, 2."
+            )],
+            warnings
+        );
+    }
 }
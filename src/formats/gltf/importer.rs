@@ -1,38 +1,132 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 
-use anyhow::Result;
-use glam::{Mat4, Quat, Vec2, Vec3, Vec3A};
-use gltf::animation::{util::ReadOutputs, Property};
+use anyhow::{anyhow, Context, Result};
+use glam::{Mat3A, Mat4, Quat, Vec2, Vec3, Vec3A, Vec4};
+use gltf::{
+    animation::{util::ReadOutputs, Interpolation, Property},
+    mesh::Semantic,
+};
 
 use crate::{
     asset::Asset,
     conversion::Importer,
+    formats::p3m::internal::MAX_NUM_BONES,
+    profile::SkeletonProfile,
     scene::{Animation, Joint, Keyframe, Mesh, Scene, Vertex},
+    skinning::{self, Influences, SkinningOptions, SkinningReport},
+};
+
+use super::{
+    accessor,
+    mapping::{JointMapping, MAPPING_EXTENSION},
+    meshopt::{self, CompressedViews},
+    uri, FRAME_OPTIONS_NODE_NAME, ROOT_MOTION_NODE_NAME,
 };
 
+/// The glTF extensions that the importer can decode. Files that require other extensions are
+/// rejected.
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_mesh_quantization", meshopt::EXTENSION_NAME];
+
+/// Draco isn't decoded, so files that require it are rejected with a hint to export them again.
+/// Files that only use it are imported from their uncompressed data.
+const DRACO_EXTENSION_NAME: &str = "KHR_draco_mesh_compression";
+
 #[derive(Default)]
-pub struct GltfImporter {}
+pub struct GltfImporter {
+    /// The mapping between skeleton nodes and scene joints. If it's `None`, the mapping is read
+    /// from the `.bonemap` file next to the imported asset, if there is one. Otherwise, the first
+    /// profile that names all the joints of the skin is used. If none does, joints are expected
+    /// to be named "bone_N".
+    pub joint_mapping: Option<JointMapping>,
+    /// The skeleton profiles used to map joint names back to joint indices.
+    pub profiles: Vec<SkeletonProfile>,
+    /// Specifies how vertices influenced by several joints are attached to a single joint.
+    pub skinning: SkinningOptions,
+    /// Merge the primitives of each glTF mesh into a single mesh. Otherwise, each primitive is
+    /// imported as a separate mesh.
+    pub merge_primitives: bool,
+}
 
 impl Importer for GltfImporter {
-    fn import(&self, asset: &Asset, scene: &mut Scene) -> Result<()> {
-        let gltf = gltf::Gltf::from_slice(&asset.bytes)?;
-        let buffers = load_buffers(&gltf, asset.path())?;
+    fn import(&self, asset: &Asset, scene: &mut Scene) -> Result<Vec<String>> {
+        // Extensions are checked before validating the document, since compressed files may
+        // not be valid without them.
+        let mut gltf = gltf::Gltf::from_slice_without_validation(&asset.bytes)?;
+        validate_extensions(&gltf)?;
+        gltf.document = gltf::Document::from_json(gltf.document.into_json())?;
+        validate_node_hierarchy(&gltf)?;
+        validate_accessors(&gltf)?;
+        let compressed_views = parse_compressed_views(&gltf, &asset.bytes)?;
+        let mut buffers = load_buffers(&gltf, asset.path(), &compressed_views)?;
+        compressed_views.decompress(&mut buffers)?;
 
-        let skin_map = make_skin_map(&gltf);
-        let joint_map = make_joint_map(&gltf);
-        let skeleton_root_index = get_skeleton_root_index(&gltf);
+        let mapping_path = asset.path().with_extension(MAPPING_EXTENSION);
+        let mapping = match &self.joint_mapping {
+            Some(mapping) => Cow::Borrowed(mapping),
+            None if mapping_path.is_file() => Cow::Owned(JointMapping::from_file(&mapping_path)?),
+            None => match find_profile(&gltf, &self.profiles) {
+                Some(profile) => Cow::Owned(JointMapping::from(profile)),
+                None => Cow::Owned(JointMapping::default()),
+            },
+        };
+
+        let skeleton_root_index = get_skeleton_root_index(&gltf, &mapping);
+        let joint_map = make_joint_map(&gltf, &mapping, skeleton_root_index)?;
+        let mut warnings = Vec::new();
+        for skin in find_disjoint_skins(&gltf) {
+            warnings.push(format!(
+                "skin {} shares no joints with skin {}. Its joints are added to the skeleton as \
+                a separate hierarchy.",
+                skin_name(&gltf, skin),
+                skin_name(&gltf, 0),
+            ));
+        }
 
         let joints = convert_joints(&gltf, &joint_map);
-        let mut meshes = convert_meshes(&gltf, &buffers, &joint_map, &skin_map);
-        let mut animations = convert_animations(&gltf, &buffers, &joint_map, skeleton_root_index);
+        let animations = convert_animations(
+            &gltf,
+            &buffers,
+            &joint_map,
+            &joints,
+            skeleton_root_index,
+            AnimationNodes {
+                root_motion: find_node(&gltf, ROOT_MOTION_NODE_NAME),
+                frame_options: find_node(&gltf, FRAME_OPTIONS_NODE_NAME),
+            },
+            &mut warnings,
+        );
 
-        scene.skeleton = joints;
-        scene.meshes.append(&mut meshes);
-        scene.animations.append(&mut animations);
+        let mut imported = Scene {
+            meshes: Vec::new(),
+            skeleton: joints,
+            animations,
+        };
+        let bind_transforms: Vec<_> = (0..imported.skeleton.len())
+            .map(|index| imported.joint_world_transform(index))
+            .collect();
+        let (meshes, influences) = convert_meshes(
+            &gltf,
+            &buffers,
+            &joint_map,
+            &bind_transforms,
+            self.merge_primitives,
+            &mut warnings,
+        )?;
+        imported.meshes = meshes;
+        imported.bake_bind_pose_rotations();
+        let report = skinning::reduce_influences(&mut imported, &influences, self.skinning);
+        warnings.extend(skinning_warning(&imported, &report));
 
-        *scene = super::transform(scene);
+        let mut imported = super::transform(&imported);
+        scene.skeleton = imported.skeleton;
+        scene.meshes.append(&mut imported.meshes);
+        scene.animations.append(&mut imported.animations);
 
-        Ok(())
+        Ok(warnings)
     }
 
     fn extensions(&self) -> &[&str] {
@@ -40,42 +134,327 @@ impl Importer for GltfImporter {
     }
 }
 
-/// Returns a mapping between joint indices (referenced by skinned vertex data)
-/// and node indices.
-fn make_skin_map(gltf: &gltf::Gltf) -> HashMap<usize, usize> {
+/// Checks that every extension required by the file is supported.
+fn validate_extensions(gltf: &gltf::Gltf) -> Result<()> {
+    match gltf
+        .extensions_required()
+        .find(|extension| !SUPPORTED_EXTENSIONS.contains(extension))
+    {
+        Some(DRACO_EXTENSION_NAME) => Err(anyhow!(
+            "Draco-compressed meshes are not supported. Export the file again without Draco \
+            compression, or with meshopt compression instead"
+        )),
+        Some(extension) => Err(anyhow!(
+            "The glTF extension \"{}\" is not supported",
+            extension
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Reads the buffer views compressed with `EXT_meshopt_compression`, which the `gltf` crate
+/// ignores, from the raw JSON document.
+fn parse_compressed_views(gltf: &gltf::Gltf, bytes: &[u8]) -> Result<CompressedViews> {
+    if !gltf
+        .extensions_used()
+        .any(|extension| extension == meshopt::EXTENSION_NAME)
+    {
+        return Ok(CompressedViews::default());
+    }
+
+    let json = if bytes.starts_with(b"glTF") {
+        gltf::Glb::from_slice(bytes)?.json
+    } else {
+        Cow::Borrowed(bytes)
+    };
+    let json: gltf::json::Value = gltf::json::deserialize::from_slice(&json)?;
+    CompressedViews::parse(&json)
+}
+
+/// Checks that every node has at most one parent and that there are no cycles in the node
+/// hierarchy.
+fn validate_node_hierarchy(gltf: &gltf::Gltf) -> Result<()> {
+    let mut child_parent_map = HashMap::new();
+    for node in gltf.nodes() {
+        for child in node.children() {
+            if child_parent_map
+                .insert(child.index(), node.index())
+                .is_some()
+            {
+                return Err(anyhow!(
+                    "Node {} is the child of more than one node",
+                    child.index()
+                ));
+            }
+        }
+    }
+
+    for node in gltf.nodes() {
+        let mut ancestor = node.index();
+        for _ in 0..child_parent_map.len() {
+            match child_parent_map.get(&ancestor) {
+                Some(&parent) => ancestor = parent,
+                None => break,
+            }
+        }
+        if child_parent_map.contains_key(&ancestor) {
+            return Err(anyhow!(
+                "Node {} is its own ancestor in the node hierarchy",
+                node.index()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the accessors read by the importer are not empty and have the types allowed by
+/// the glTF specification for their usage.
+fn validate_accessors(gltf: &gltf::Gltf) -> Result<()> {
+    use gltf::accessor::{DataType::*, Dimensions::*};
+
+    let check = |accessor: gltf::Accessor,
+                 usage: &dyn Fn() -> String,
+                 dimensions: gltf::accessor::Dimensions,
+                 data_types: &[gltf::accessor::DataType]| {
+        let sparse_count = accessor.sparse().map_or(1, |sparse| sparse.count());
+        if accessor.count() == 0 || sparse_count == 0 {
+            return Err(anyhow!(
+                "Accessor {} ({}) is empty",
+                accessor.index(),
+                usage()
+            ));
+        }
+        if accessor.dimensions() != dimensions || !data_types.contains(&accessor.data_type()) {
+            return Err(anyhow!(
+                "Accessor {} ({}) has an unsupported type: {:?} of {:?}",
+                accessor.index(),
+                usage(),
+                accessor.dimensions(),
+                accessor.data_type()
+            ));
+        }
+        Ok(())
+    };
+
+    for mesh in gltf.meshes() {
+        let name = mesh.name().unwrap_or_default();
+        for primitive in mesh.primitives() {
+            for (semantic, accessor) in primitive.attributes() {
+                let usage = || format!("{:?} of mesh \"{}\"", semantic, name);
+                match semantic {
+                    Semantic::Positions => check(accessor, &usage, Vec3, &[F32, I8, U8, I16, U16])?,
+                    Semantic::Normals => check(accessor, &usage, Vec3, &[F32, I8, I16])?,
+                    Semantic::TexCoords(0) => {
+                        check(accessor, &usage, Vec2, &[F32, I8, U8, I16, U16])?
+                    }
+                    Semantic::Joints(0) => check(accessor, &usage, Vec4, &[U8, U16])?,
+                    Semantic::Weights(0) => check(accessor, &usage, Vec4, &[F32, U8, U16])?,
+                    _ => {}
+                }
+            }
+            if let Some(accessor) = primitive.indices() {
+                let usage = || format!("indices of mesh \"{}\"", name);
+                check(accessor, &usage, Scalar, &[U8, U16, U32])?;
+            }
+        }
+    }
+
+    for skin in gltf.skins() {
+        if let Some(accessor) = skin.inverse_bind_matrices() {
+            let usage = || format!("inverse bind matrices of skin {}", skin.index());
+            check(accessor, &usage, Mat4, &[F32])?;
+        }
+    }
+
+    for animation in gltf.animations() {
+        let name = animation.name().unwrap_or_default();
+        for (index, channel) in animation.channels().enumerate() {
+            let usage = || format!("channel {} of animation \"{}\"", index, name);
+            let sampler = channel.sampler();
+            check(sampler.input(), &usage, Scalar, &[F32])?;
+            match channel.target().property() {
+                Property::Translation | Property::Scale => {
+                    check(sampler.output(), &usage, Vec3, &[F32])?
+                }
+                Property::Rotation => {
+                    check(sampler.output(), &usage, Vec4, &[F32, I8, U8, I16, U16])?
+                }
+                Property::MorphTargetWeights => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the joints of every skin in order of appearance. Joints shared by several skins are
+/// only returned once.
+fn get_skin_joints(gltf: &gltf::Gltf) -> Vec<gltf::Node<'_>> {
+    let mut visited = HashSet::new();
     gltf.skins()
-        .next()
-        .map(|skin| {
-            skin.joints()
-                .enumerate()
-                .map(|(index, node)| (index, node.index()))
-                .collect()
-        })
-        .unwrap_or_default()
+        .flat_map(|skin| skin.joints())
+        .filter(|node| visited.insert(node.index()))
+        .collect()
+}
+
+/// Returns the indices of the skins that are not connected to the first skin, either by
+/// sharing joints or through the parents of their joints. Such skins are merged into the scene
+/// skeleton as separate hierarchies.
+fn find_disjoint_skins(gltf: &gltf::Gltf) -> Vec<usize> {
+    let mut child_parent_map = HashMap::new();
+    for node in gltf.nodes() {
+        for child in node.children() {
+            child_parent_map.insert(child.index(), node.index());
+        }
+    }
+
+    let skins: Vec<HashSet<_>> = gltf
+        .skins()
+        .map(|skin| skin.joints().map(|node| node.index()).collect())
+        .collect();
+    let Some(first) = skins.first() else {
+        return Vec::new();
+    };
+
+    let mut connected = first.clone();
+    let mut disjoint: Vec<_> = (1..skins.len()).collect();
+    loop {
+        let num_disjoint = disjoint.len();
+        disjoint.retain(|&skin| {
+            let joints = &skins[skin];
+            let is_child_of = |node: &usize, joints: &HashSet<usize>| {
+                child_parent_map
+                    .get(node)
+                    .is_some_and(|parent| joints.contains(parent))
+            };
+            let is_connected = joints
+                .iter()
+                .any(|node| connected.contains(node) || is_child_of(node, &connected))
+                || connected.iter().any(|node| is_child_of(node, joints));
+            if is_connected {
+                connected.extend(joints);
+            }
+            !is_connected
+        });
+        if disjoint.len() == num_disjoint {
+            return disjoint;
+        }
+    }
 }
 
 /// Returns a mapping between GLTF node indices and joint indices from the
-/// internal scene representation. Only joints named "bone_XX" are considered.
-fn make_joint_map(gltf: &gltf::Gltf) -> HashMap<usize, usize> {
-    gltf.nodes()
-        .filter_map(|node| {
-            let node_name = node.name().unwrap_or_default();
-            if let Some(stripped) = node_name.strip_prefix("bone_") {
-                if let Ok(joint_index) = stripped.parse() {
-                    return Some((node.index(), joint_index));
+/// internal scene representation, according to the given joint mapping.
+/// Every joint of every skin, except for the skeleton root, must be mapped.
+fn make_joint_map(
+    gltf: &gltf::Gltf,
+    mapping: &JointMapping,
+    skeleton_root_index: Option<usize>,
+) -> Result<HashMap<usize, usize>> {
+    let skin_joints: Vec<_> = get_skin_joints(gltf)
+        .into_iter()
+        .filter(|node| Some(node.index()) != skeleton_root_index)
+        .collect();
+
+    let joint_map: HashMap<usize, usize> = match mapping {
+        JointMapping::BoneNames => gltf
+            .nodes()
+            .filter_map(|node| {
+                let node_name = node.name().unwrap_or_default();
+                if let Some(stripped) = node_name.strip_prefix("bone_") {
+                    if let Ok(joint_index) = stripped.parse() {
+                        return Some((node.index(), joint_index));
+                    }
                 }
+                None
+            })
+            .collect(),
+        JointMapping::SkinOrder => skin_joints
+            .iter()
+            .enumerate()
+            .map(|(joint_index, node)| (node.index(), joint_index))
+            .collect(),
+        JointMapping::Names { joints, .. } => gltf
+            .nodes()
+            .filter_map(|node| {
+                let joint_index = joints.get(node.name().unwrap_or_default())?;
+                Some((node.index(), *joint_index))
+            })
+            .collect(),
+    };
+
+    if let Some(joint_index) = joint_map.values().find(|&&index| index >= MAX_NUM_BONES) {
+        return Err(anyhow!(
+            "The joint \"bone_{}\" exceeds the maximum number of joints ({})",
+            joint_index,
+            MAX_NUM_BONES
+        ));
+    }
+
+    let mut mapped_joints = HashSet::new();
+    for node in gltf.nodes() {
+        if let Some(joint_index) = joint_map.get(&node.index()) {
+            if !mapped_joints.insert(joint_index) {
+                return Err(anyhow!(
+                    "More than one node is mapped to the joint \"bone_{}\"",
+                    joint_index
+                ));
             }
-            None
+        }
+    }
+
+    let unmapped: Vec<_> = skin_joints
+        .iter()
+        .filter(|node| !joint_map.contains_key(&node.index()))
+        .map(|node| match node.name() {
+            Some(name) => format!("\"{}\"", name),
+            None => format!("<node {}>", node.index()),
         })
-        .collect()
+        .collect();
+    if !unmapped.is_empty() {
+        return Err(anyhow!(
+            "The following skin joints could not be mapped to a scene joint: {}. \
+            Rename them to \"bone_N\" or provide a .{} file",
+            unmapped.join(", "),
+            MAPPING_EXTENSION
+        ));
+    }
+
+    Ok(joint_map)
 }
 
-/// Returns the index of the skeleton root node. The skeleton of the root
-/// node is the first node whose name is "root". This node is used to
-/// apply translations to the whole skeleton in animations.
-fn get_skeleton_root_index(gltf: &gltf::Gltf) -> Option<usize> {
+/// Returns the first profile that names every joint of the skins, except for the skeleton root.
+fn find_profile<'a>(
+    gltf: &gltf::Gltf,
+    profiles: &'a [SkeletonProfile],
+) -> Option<&'a SkeletonProfile> {
+    let skin_joints = get_skin_joints(gltf);
+    if skin_joints.is_empty() {
+        return None;
+    }
+    profiles.iter().find(|profile| {
+        skin_joints
+            .iter()
+            .filter_map(|node| node.name())
+            .filter(|&name| name != JointMapping::default().root_name())
+            .all(|name| profile.joint_index(name).is_some())
+    })
+}
+
+/// Returns the name of a skin for display purposes.
+fn skin_name(gltf: &gltf::Gltf, index: usize) -> String {
+    match gltf.skins().nth(index).and_then(|skin| skin.name()) {
+        Some(name) => format!("\"{}\"", name),
+        None => format!("<skin {}>", index),
+    }
+}
+
+/// Returns the index of the skeleton root node. The skeleton root is the first
+/// node whose name matches the root name of the joint mapping ("root" by default).
+/// This node is used to apply translations to the whole skeleton in animations.
+fn get_skeleton_root_index(gltf: &gltf::Gltf, mapping: &JointMapping) -> Option<usize> {
     gltf.nodes().find_map(|node| {
-        if node.name().unwrap_or_default() == "root" {
+        if node.name().unwrap_or_default() == mapping.root_name() {
             Some(node.index())
         } else {
             None
@@ -83,6 +462,21 @@ fn get_skeleton_root_index(gltf: &gltf::Gltf) -> Option<usize> {
     })
 }
 
+/// Returns the index of the first node with the given name.
+fn find_node(gltf: &gltf::Gltf, name: &str) -> Option<usize> {
+    gltf.nodes()
+        .find(|node| node.name() == Some(name))
+        .map(|node| node.index())
+}
+
+/// The nodes that store animation data kept apart from the skeleton.
+struct AnimationNodes {
+    /// The node animated with [`Keyframe::root_motion`].
+    root_motion: Option<usize>,
+    /// The node whose X coordinate is animated with [`Keyframe::option`].
+    frame_options: Option<usize>,
+}
+
 fn convert_joints(gltf: &gltf::Gltf, joint_map: &HashMap<usize, usize>) -> Vec<Joint> {
     // Compute a mapping between child nodes and their parents. This is necessary because
     // the intermediary joint representation keeps track of the each joint's parent.
@@ -116,110 +510,177 @@ fn convert_joints(gltf: &gltf::Gltf, joint_map: &HashMap<usize, usize>) -> Vec<J
     joints
 }
 
-/// The animation input time should already be sampled at 55 FPS. All channels should be
-/// the same length. Joint translations and scales are ignored.
+/// The maximum number of frames of an animation, as limited by the game formats.
+const MAX_NUM_FRAMES: usize = u16::MAX as usize;
+
+/// Every channel is resampled according to its interpolation mode, starting at the earliest
+/// keyframe of the animation. If every channel is linearly interpolated and has a keyframe on
+/// every frame of a common evenly spaced timeline, its frame rate is kept. Otherwise, the
+/// animation is sampled at 55 FPS, so that the curves of the channels are preserved. Skipped
+/// channels and animations are added to `warnings`.
 fn convert_animations(
     gltf: &gltf::Gltf,
     buffers: &[Vec<u8>],
     joint_map: &HashMap<usize, usize>,
+    joints: &[Joint],
     skeleton_root_index: Option<usize>,
+    nodes: AnimationNodes,
+    warnings: &mut Vec<String>,
 ) -> Vec<Animation> {
+    let num_joints = joint_map.values().max().map_or(0, |&index| index + 1);
+
     let mut result = Vec::new();
     for animation in gltf.animations() {
-        let mut root_translations: Vec<Vec3> = Vec::new();
-        // Dimensions: [joint, frame, value]
-        let mut translations: Vec<Vec<Vec3>> = vec![Vec::new(); joint_map.len()];
-        let mut rotations: Vec<Vec<Quat>> = vec![Vec::new(); joint_map.len()];
-        let mut scales: Vec<Vec<Vec3>> = vec![Vec::new(); joint_map.len()];
-
-        let mut num_frames = 0;
-        for channel in animation.channels() {
+        let mut root_translations: Option<Sampler<Vec3>> = None;
+        let mut root_motions: Option<Sampler<Vec3>> = None;
+        let mut frame_options: Option<Sampler<Vec3>> = None;
+        // Dimensions: [joint]
+        let mut translations: Vec<Option<Sampler<Vec3>>> = vec![None; num_joints];
+        let mut rotations: Vec<Option<Sampler<Quat>>> = vec![None; num_joints];
+        let mut scales: Vec<Option<Sampler<Vec3>>> = vec![None; num_joints];
+
+        for (channel_index, channel) in animation.channels().enumerate() {
             let index = channel.target().node().index();
-            if Some(index) == skeleton_root_index
-                && channel.target().property() == Property::Translation
-            {
-                // Root translations
-                let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
-                root_translations = reader
-                    .read_outputs()
-                    .map(|v| match v {
-                        ReadOutputs::Translations(v) => v.map(|x| x.into()).collect(),
-                        _ => Vec::new(),
-                    })
-                    .unwrap_or_default();
-                num_frames = num_frames.max(root_translations.len());
-            } else if joint_map.contains_key(&index) {
-                // Joint transforms
-                let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
-                match channel.target().property() {
-                    Property::Translation => {
-                        translations[*joint_map.get(&index).unwrap()] = reader
-                            .read_outputs()
-                            .map(|v| match v {
-                                ReadOutputs::Translations(v) => v.map(|x| x.into()).collect(),
-                                _ => Vec::new(),
-                            })
-                            .unwrap_or_default();
-                        num_frames =
-                            num_frames.max(translations[*joint_map.get(&index).unwrap()].len());
+            let property = channel.target().property();
+            let interpolation = channel.sampler().interpolation();
+
+            let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+            let times: Vec<f32> = reader
+                .read_inputs()
+                .map(|v| v.collect())
+                .unwrap_or_default();
+            let outputs = reader.read_outputs();
+
+            let is_valid =
+                if Some(index) == skeleton_root_index && property == Property::Translation {
+                    // Root translations
+                    if let Some(ReadOutputs::Translations(v)) = outputs {
+                        let values = v.map(Vec3::from).collect();
+                        root_translations = Sampler::new(times, values, interpolation);
                     }
-                    Property::Rotation => {
-                        rotations[*joint_map.get(&index).unwrap()] = reader
-                            .read_outputs()
-                            .map(|v| match v {
-                                ReadOutputs::Rotations(v) => {
-                                    v.into_f32().map(Quat::from_array).collect()
-                                }
-                                _ => Vec::new(),
-                            })
-                            .unwrap_or_default();
-                        num_frames =
-                            num_frames.max(rotations[*joint_map.get(&index).unwrap()].len());
+                    root_translations.is_some()
+                } else if Some(index) == nodes.root_motion && property == Property::Translation {
+                    if let Some(ReadOutputs::Translations(v)) = outputs {
+                        let values = v.map(Vec3::from).collect();
+                        root_motions = Sampler::new(times, values, interpolation);
                     }
-                    Property::Scale => {
-                        scales[*joint_map.get(&index).unwrap()] = reader
-                            .read_outputs()
-                            .map(|v| match v {
-                                ReadOutputs::Scales(v) => v.map(|x| x.into()).collect(),
-                                _ => Vec::new(),
-                            })
-                            .unwrap_or_default();
-                        num_frames = num_frames.max(scales[*joint_map.get(&index).unwrap()].len());
+                    root_motions.is_some()
+                } else if Some(index) == nodes.frame_options && property == Property::Translation {
+                    if let Some(ReadOutputs::Translations(v)) = outputs {
+                        let values = v.map(Vec3::from).collect();
+                        frame_options = Sampler::new(times, values, interpolation);
                     }
-                    _ => {}
-                }
+                    frame_options.is_some()
+                } else if let Some(&joint_index) = joint_map.get(&index) {
+                    // Joint transforms
+                    match outputs {
+                        Some(ReadOutputs::Translations(v)) => {
+                            let values = v.map(Vec3::from).collect();
+                            translations[joint_index] = Sampler::new(times, values, interpolation);
+                            translations[joint_index].is_some()
+                        }
+                        Some(ReadOutputs::Rotations(v)) => {
+                            let values = v.into_f32().map(Quat::from_array).collect();
+                            rotations[joint_index] = Sampler::new(times, values, interpolation);
+                            rotations[joint_index].is_some()
+                        }
+                        Some(ReadOutputs::Scales(v)) => {
+                            let values = v.map(Vec3::from).collect();
+                            scales[joint_index] = Sampler::new(times, values, interpolation);
+                            scales[joint_index].is_some()
+                        }
+                        _ => true,
+                    }
+                } else {
+                    true
+                };
+
+            if !is_valid {
+                warnings.push(format!(
+                    "skipped channel {} of animation \"{}\" because its keyframes are invalid.",
+                    channel_index,
+                    animation.name().unwrap_or_default()
+                ));
             }
         }
 
+        let timelines: Vec<Timeline> = root_translations
+            .iter()
+            .chain(&root_motions)
+            .chain(&frame_options)
+            .chain(translations.iter().flatten())
+            .chain(scales.iter().flatten())
+            .map(Sampler::timeline)
+            .chain(rotations.iter().flatten().map(Sampler::timeline))
+            .collect();
+
+        // Compute the time span covered by all the channels of the animation.
+        let start = timelines
+            .iter()
+            .map(|timeline| timeline.times[0])
+            .reduce(f32::min)
+            .unwrap_or_default();
+        let end = timelines
+            .iter()
+            .map(|timeline| timeline.times[timeline.times.len() - 1])
+            .reduce(f32::max)
+            .unwrap_or_default();
+
+        let sampling_rate =
+            detect_sampling_rate(&timelines, start).unwrap_or(DEFAULT_SAMPLING_RATE);
+        let num_frames = if !timelines.is_empty() {
+            (((end - start) * sampling_rate).round() as usize).saturating_add(1)
+        } else {
+            0
+        };
+        if num_frames > MAX_NUM_FRAMES {
+            warnings.push(format!(
+                "skipped animation \"{}\" because it has more than {} frames.",
+                animation.name().unwrap_or_default(),
+                MAX_NUM_FRAMES
+            ));
+            continue;
+        }
+
         let frames = (0..num_frames)
             .map(|i| {
-                let root_translation = root_translations.get(i).copied().unwrap_or_default();
-                let num_transforms = joint_map.len();
-                let transforms: Vec<Mat4> = (0..num_transforms)
+                let time = start + i as f32 / sampling_rate;
+                let root_translation = root_translations
+                    .as_ref()
+                    .map(|sampler| sampler.sample(time))
+                    .unwrap_or_default();
+                let root_motion = root_motions
+                    .as_ref()
+                    .map(|sampler| sampler.sample(time))
+                    .unwrap_or_default();
+                // Editors may resample the marker track, so the options are rounded.
+                let option = frame_options
+                    .as_ref()
+                    .map(|sampler| sampler.sample(time).x.round().clamp(0., u8::MAX as f32) as u8)
+                    .unwrap_or_default();
+                let transforms: Vec<Mat4> = (0..num_joints)
                     .map(|j| {
-                        let _translation = translations
-                            .get(j)
-                            .and_then(|v| v.get(i))
-                            .copied()
+                        // Joint matrices store translations as offsets from the bind pose.
+                        let translation = translations[j]
+                            .as_ref()
+                            .map(|sampler| sampler.sample(time) - Vec3::from(joints[j].translation))
                             .unwrap_or_default();
-                        let rotation = rotations
-                            .get(j)
-                            .and_then(|v| v.get(i))
-                            .copied()
-                            .unwrap_or(Quat::IDENTITY);
-                        let _scale = scales
-                            .get(j)
-                            .and_then(|v| v.get(i))
-                            .copied()
-                            .unwrap_or_else(|| Vec3::new(1., 1., 1.));
-
-                        // Currently, translation and scale are ignored. Only rotation
-                        // is taken into account.
-                        Mat4::from_rotation_translation(rotation, Vec3::ZERO)
+                        let rotation = rotations[j]
+                            .as_ref()
+                            .map(|sampler| sampler.sample(time))
+                            .unwrap_or(joints[j].rotation);
+                        let scale = scales[j]
+                            .as_ref()
+                            .map(|sampler| sampler.sample(time))
+                            .unwrap_or(Vec3::ONE);
+
+                        Mat4::from_scale_rotation_translation(scale, rotation, translation)
                     })
                     .collect();
                 Keyframe {
                     translation: root_translation.into(),
+                    root_motion: root_motion.into(),
+                    option,
                     transforms,
                 }
             })
@@ -227,22 +688,333 @@ fn convert_animations(
 
         result.push(Animation {
             name: animation.name().unwrap_or_default().to_string(),
+            sampling_rate,
             frames,
         })
     }
     result
 }
 
+/// The frame rate used to sample animations without a regular timeline. It matches the frame
+/// rate of the game.
+const DEFAULT_SAMPLING_RATE: f32 = 55.;
+
+/// The keyframe times of an animation channel and how they're interpolated.
+struct Timeline<'a> {
+    times: &'a [f32],
+    interpolation: Interpolation,
+}
+
+/// Returns the frame rate of the given timelines if all of them are linearly interpolated and
+/// have a keyframe on every frame of a common evenly spaced timeline that begins at `start`,
+/// with at least one frame per second. Otherwise, returns `None`.
+///
+/// Sampling other timelines at the times of their keyframes would lose the curve between them,
+/// since the keyframes are linearly interpolated afterwards.
+fn detect_sampling_rate(timelines: &[Timeline], start: f32) -> Option<f32> {
+    const TOLERANCE: f32 = 1e-2;
+
+    if timelines
+        .iter()
+        .any(|timeline| timeline.interpolation != Interpolation::Linear)
+    {
+        return None;
+    }
+
+    let step = timelines
+        .iter()
+        .flat_map(|timeline| timeline.times.windows(2).map(|pair| pair[1] - pair[0]))
+        .filter(|&delta| delta > f32::EPSILON)
+        .reduce(f32::min)?;
+
+    // Timelines sparser than one frame per second are sampled at the default rate instead.
+    if step > 1. {
+        return None;
+    }
+
+    // Every keyframe must lie on the timeline, right after the previous one.
+    let is_dense = timelines.iter().all(|timeline| {
+        let positions: Vec<_> = timeline
+            .times
+            .iter()
+            .map(|&time| (time - start) / step)
+            .collect();
+        positions
+            .iter()
+            .all(|position| (position - position.round()).abs() < TOLERANCE)
+            && positions
+                .windows(2)
+                .all(|pair| pair[1].round() - pair[0].round() == 1.)
+    });
+    if !is_dense {
+        return None;
+    }
+
+    let rate = 1. / step;
+
+    // Avoid rates such as 29.999998 FPS due to floating point errors.
+    if (rate - rate.round()).abs() < TOLERANCE {
+        Some(rate.round())
+    } else {
+        Some(rate)
+    }
+}
+
+/// The keyframes of a single animation channel, which can be sampled at any point in time.
+#[derive(Debug, Clone)]
+struct Sampler<T> {
+    times: Vec<f32>,
+    /// The output values. For cubic spline interpolation, each keyframe is comprised of three
+    /// consecutive elements: in-tangent, value, and out-tangent.
+    values: Vec<T>,
+    interpolation: Interpolation,
+}
+
+impl<T> Sampler<T> {
+    fn timeline(&self) -> Timeline<'_> {
+        Timeline {
+            times: &self.times,
+            interpolation: self.interpolation,
+        }
+    }
+}
+
+impl<T: Interpolate> Sampler<T> {
+    /// Creates a new sampler. Returns `None` if there are no keyframes, the keyframe times are
+    /// not finite and increasing, or the number of values does not match the number of
+    /// keyframes.
+    fn new(times: Vec<f32>, values: Vec<T>, interpolation: Interpolation) -> Option<Self> {
+        let values_per_key = match interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+        if times.is_empty() || values.len() != times.len() * values_per_key {
+            return None;
+        }
+        if times.iter().any(|time| !time.is_finite()) || times.windows(2).any(|w| w[0] > w[1]) {
+            return None;
+        }
+
+        Some(Self {
+            times,
+            values,
+            interpolation,
+        })
+    }
+
+    fn start(&self) -> f32 {
+        self.times[0]
+    }
+
+    fn end(&self) -> f32 {
+        self.times[self.times.len() - 1]
+    }
+
+    /// Returns the value of the keyframe with the given index.
+    fn value(&self, index: usize) -> T {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[index * 3 + 1],
+            _ => self.values[index],
+        }
+    }
+
+    /// Returns the interpolated value of the channel at the given time. Times outside of the
+    /// keyframe range are clamped.
+    fn sample(&self, time: f32) -> T {
+        if time <= self.start() {
+            return self.value(0);
+        }
+        if time >= self.end() {
+            return self.value(self.times.len() - 1);
+        }
+
+        // Index of the keyframe immediately before the given time.
+        let prev = self.times.partition_point(|&t| t <= time) - 1;
+        let next = prev + 1;
+        let delta = self.times[next] - self.times[prev];
+        let factor = if delta > 0. {
+            (time - self.times[prev]) / delta
+        } else {
+            0.
+        };
+
+        match self.interpolation {
+            Interpolation::Step => self.value(prev),
+            Interpolation::Linear => self.value(prev).lerp(self.value(next), factor),
+            Interpolation::CubicSpline => T::hermite(
+                self.value(prev),
+                self.values[prev * 3 + 2].scale(delta),
+                self.value(next),
+                self.values[next * 3].scale(delta),
+                factor,
+            ),
+        }
+    }
+}
+
+/// Defines a type that can be interpolated between animation keyframes.
+trait Interpolate: Copy {
+    fn lerp(self, other: Self, factor: f32) -> Self;
+    fn scale(self, factor: f32) -> Self;
+    /// Evaluates the cubic Hermite spline with the given points and tangents, as defined in
+    /// the appendix C of the glTF specification.
+    fn hermite(p0: Self, m0: Self, p1: Self, m1: Self, factor: f32) -> Self;
+}
+
+/// Returns the weights of the points and tangents of a cubic Hermite spline.
+fn hermite_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        2. * t3 - 3. * t2 + 1.,
+        t3 - 2. * t2 + t,
+        -2. * t3 + 3. * t2,
+        t3 - t2,
+    ]
+}
+
+impl Interpolate for Vec3 {
+    fn lerp(self, other: Self, factor: f32) -> Self {
+        Vec3::lerp(self, other, factor)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self * factor
+    }
+
+    fn hermite(p0: Self, m0: Self, p1: Self, m1: Self, factor: f32) -> Self {
+        let [w0, w1, w2, w3] = hermite_weights(factor);
+        p0 * w0 + m0 * w1 + p1 * w2 + m1 * w3
+    }
+}
+
+impl Interpolate for Quat {
+    fn lerp(self, other: Self, factor: f32) -> Self {
+        self.slerp(other, factor)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self * factor
+    }
+
+    fn hermite(p0: Self, m0: Self, p1: Self, m1: Self, factor: f32) -> Self {
+        let [w0, w1, w2, w3] = hermite_weights(factor);
+        let value =
+            Vec4::from(p0) * w0 + Vec4::from(m0) * w1 + Vec4::from(p1) * w2 + Vec4::from(m1) * w3;
+        Quat::from_vec4(value).normalize()
+    }
+}
+
+/// A mesh placed in the scene by a node.
+struct MeshInstance<'a> {
+    mesh: gltf::Mesh<'a>,
+    /// The transform of the node, relative to the origin of the scene.
+    transform: Mat4,
+    /// The skin of the node. Meshes of nodes without a skin are bound to the first skin.
+    skin: Option<gltf::Skin<'a>>,
+}
+
+/// Walks the nodes of the default scene (or the first scene) and returns every mesh they
+/// instantiate. If the file has no scenes, all the nodes without a parent are walked instead.
+fn find_mesh_instances(gltf: &gltf::Gltf) -> Vec<MeshInstance<'_>> {
+    fn visit<'a>(
+        node: gltf::Node<'a>,
+        parent_transform: Mat4,
+        instances: &mut Vec<MeshInstance<'a>>,
+        gltf: &'a gltf::Gltf,
+    ) {
+        let transform =
+            parent_transform.mul_mat4(&Mat4::from_cols_array_2d(&node.transform().matrix()));
+        if let Some(mesh) = node.mesh() {
+            instances.push(MeshInstance {
+                mesh,
+                transform,
+                skin: node.skin().or_else(|| gltf.skins().next()),
+            });
+        }
+        for child in node.children() {
+            visit(child, transform, instances, gltf);
+        }
+    }
+
+    let roots: Vec<_> = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+        Some(scene) => scene.nodes().collect(),
+        None => {
+            let children: HashSet<_> = gltf
+                .nodes()
+                .flat_map(|node| node.children())
+                .map(|node| node.index())
+                .collect();
+            gltf.nodes()
+                .filter(|node| !children.contains(&node.index()))
+                .collect()
+        }
+    };
+
+    let mut instances = Vec::new();
+    for root in roots {
+        visit(root, Mat4::IDENTITY, &mut instances, gltf);
+    }
+    instances
+}
+
+/// Returns the converted meshes and the joint influences of each of their vertices. The joint
+/// of each vertex is left unassigned.
+///
+/// Vertices are moved to the bind pose of the scene skeleton, given by the bind transform of
+/// each joint. Skinned vertices are placed by the inverse bind matrices of their skin, and the
+/// other vertices by the transform of their node. Each primitive becomes a separate mesh,
+/// unless `merge_primitives` is set.
 fn convert_meshes(
     gltf: &gltf::Gltf,
     buffers: &[Vec<u8>],
     joint_map: &HashMap<usize, usize>,
-    skin_map: &HashMap<usize, usize>,
-) -> Vec<Mesh> {
+    bind_transforms: &[Mat4],
+    merge_primitives: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(Vec<Mesh>, Vec<Vec<Influences>>)> {
     let mut meshes = Vec::new();
-    for mesh in gltf.meshes() {
-        let name = mesh.name().unwrap_or_default();
-        for primitive in mesh.primitives() {
+    let mut influences = Vec::new();
+    for instance in find_mesh_instances(gltf) {
+        let name = instance.mesh.name().unwrap_or_default();
+
+        // Skin joints are converted into scene joints. Unmapped joints, such as the skeleton
+        // root, are discarded.
+        let mut skin_joints = Vec::new();
+        let mut skin_transforms = Vec::new();
+        if let Some(skin) = &instance.skin {
+            let inverse_bind_matrices: Vec<_> = skin
+                .reader(|buffer| Some(&buffers[buffer.index()]))
+                .read_inverse_bind_matrices()
+                .map(|v| v.map(|x| Mat4::from_cols_array_2d(&x)).collect())
+                .unwrap_or_default();
+            for (index, node) in skin.joints().enumerate() {
+                let joint = joint_map.get(&node.index()).copied();
+                let bind_transform = joint
+                    .and_then(|joint| bind_transforms.get(joint))
+                    .copied()
+                    .unwrap_or_default();
+                let inverse_bind_matrix = inverse_bind_matrices
+                    .get(index)
+                    .copied()
+                    .unwrap_or_default();
+                skin_joints.push(joint);
+                skin_transforms.push(bind_transform.mul_mat4(&inverse_bind_matrix));
+            }
+        }
+
+        let mut primitives = Vec::new();
+        for primitive in instance.mesh.primitives() {
+            if triangulate(primitive.mode(), Vec::new()).is_none() {
+                warnings.push(format!(
+                    "skipped a primitive of mesh \"{}\" because its mode ({:?}) is not made of \
+                    triangles.",
+                    name,
+                    primitive.mode()
+                ));
+                continue;
+            }
+
             let mut mesh = Mesh {
                 name: name.into(),
                 ..Default::default()
@@ -250,17 +1022,21 @@ fn convert_meshes(
 
             let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
-            let positions: Vec<Vec3A> = reader
-                .read_positions()
-                .map(|v| v.map(|x| x.into()).collect())
+            // Vertex attributes may be quantized (KHR_mesh_quantization).
+            let positions: Vec<Vec3A> = primitive
+                .get(&Semantic::Positions)
+                .and_then(|accessor| accessor::read_floats::<3>(accessor, buffers))
+                .map(|v| v.into_iter().map(Vec3A::from).collect())
                 .unwrap_or_default();
-            let normals: Vec<Vec3A> = reader
-                .read_normals()
-                .map(|v| v.map(|x| x.into()).collect())
+            let normals: Vec<Vec3A> = primitive
+                .get(&Semantic::Normals)
+                .and_then(|accessor| accessor::read_floats::<3>(accessor, buffers))
+                .map(|v| v.into_iter().map(Vec3A::from).collect())
                 .unwrap_or_default();
-            let tex_coords: Vec<Vec2> = reader
-                .read_tex_coords(0)
-                .map(|v| v.into_f32().map(|x| x.into()).collect())
+            let tex_coords: Vec<Vec2> = primitive
+                .get(&Semantic::TexCoords(0))
+                .and_then(|accessor| accessor::read_floats::<2>(accessor, buffers))
+                .map(|v| v.into_iter().map(Vec2::from).collect())
                 .unwrap_or_default();
             let joints: Vec<_> = reader
                 .read_joints(0)
@@ -270,78 +1046,213 @@ fn convert_meshes(
                 .read_weights(0)
                 .map(|v| v.into_f32().collect())
                 .unwrap_or_default();
+            // Non-indexed primitives use every vertex in order.
             let indices: Vec<_> = reader
                 .read_indices()
                 .map(|v| v.into_u32().map(|x| x as usize).collect())
-                .unwrap_or_default();
+                .unwrap_or_else(|| (0..positions.len()).collect());
+            if let Some(&index) = indices.iter().find(|&&index| index >= positions.len()) {
+                return Err(anyhow!(
+                    "Mesh \"{}\" references vertex {}, but it only has {} vertices",
+                    name,
+                    index,
+                    positions.len()
+                ));
+            }
+            let mut indices = triangulate(primitive.mode(), indices).unwrap_or_default();
+
+            // Mirroring transforms reverse the winding order of the triangles.
+            if instance.transform.determinant() < 0. {
+                for triangle in indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+
+            let mut mesh_influences = Vec::with_capacity(positions.len());
+            for (index, &position) in positions.iter().enumerate() {
+                let joints: [u16; 4] = joints.get(index).cloned().unwrap_or_default();
+                let weights: [f32; 4] = weights.get(index).cloned().unwrap_or_default();
 
-            mesh.vertices = (0..positions.len())
-                .map(|index| {
-                    let position = positions[index];
-                    let normal = normals.get(index).cloned().unwrap_or_default();
-                    let uv = tex_coords.get(index).cloned().unwrap_or_default();
-                    let joints = joints.get(index).cloned().unwrap_or_default();
-                    let weights = weights.get(index).cloned().unwrap_or_default();
-
-                    // Chooses the joint with maximum influence over the vertex.
-                    let (joint, weight) = joints
-                        .iter()
-                        .zip(weights)
-                        .max_by(|(_, w_a), (_, w_b)| {
-                            w_a.partial_cmp(w_b).unwrap_or(std::cmp::Ordering::Equal)
-                        })
-                        .unwrap();
-                    let joint = if weight > 0.0 {
-                        let joint = skin_map.get(&(*joint as usize)).unwrap();
-                        joint_map.get(joint).copied()
-                    } else {
-                        None
-                    };
-
-                    Vertex {
-                        position,
-                        normal,
-                        uv,
-                        joint,
+                let mut transform = Mat4::ZERO;
+                let mut total_weight = 0.;
+                let mut vertex_influences = Influences::new();
+                for (&joint, weight) in joints.iter().zip(weights) {
+                    let joint = joint as usize;
+                    if weight <= 0. || joint >= skin_transforms.len() {
+                        continue;
                     }
-                })
-                .collect();
+                    transform += skin_transforms[joint] * weight;
+                    total_weight += weight;
+                    if let Some(joint) = skin_joints[joint] {
+                        vertex_influences.push((joint, weight));
+                    }
+                }
+                let transform = if total_weight > 0. {
+                    transform * total_weight.recip()
+                } else {
+                    instance.transform
+                };
+
+                let normal = normals.get(index).cloned().unwrap_or_default();
+                let normal_matrix = Mat3A::from_mat4(transform).inverse().transpose();
+                mesh.vertices.push(Vertex {
+                    position: transform.transform_point3a(position),
+                    normal: normal_matrix.mul_vec3a(normal).normalize_or_zero(),
+                    uv: tex_coords.get(index).cloned().unwrap_or_default(),
+                    joint: None,
+                });
+                mesh_influences.push(vertex_influences);
+            }
             mesh.indices = indices;
 
+            primitives.push((mesh, mesh_influences));
+        }
+
+        if merge_primitives && !primitives.is_empty() {
+            primitives = vec![merge_meshes(primitives)];
+        }
+        for (mesh, mesh_influences) in primitives {
             meshes.push(mesh);
+            influences.push(mesh_influences);
         }
     }
-    meshes
+    Ok((meshes, influences))
+}
+
+/// Merges several meshes and the influences of their vertices into a single mesh, which takes
+/// the name of the first one.
+fn merge_meshes(meshes: Vec<(Mesh, Vec<Influences>)>) -> (Mesh, Vec<Influences>) {
+    let mut meshes = meshes.into_iter();
+    let (mut merged, mut merged_influences) = meshes.next().unwrap_or_default();
+    for (mesh, mut influences) in meshes {
+        let offset = merged.vertices.len();
+        merged.vertices.extend(mesh.vertices);
+        merged
+            .indices
+            .extend(mesh.indices.iter().map(|index| index + offset));
+        merged_influences.append(&mut influences);
+    }
+    (merged, merged_influences)
+}
+
+/// Converts the indices of a primitive into a triangle list. Returns `None` if the primitive
+/// is not made of triangles.
+fn triangulate(mode: gltf::mesh::Mode, indices: Vec<usize>) -> Option<Vec<usize>> {
+    use gltf::mesh::Mode;
+
+    match mode {
+        Mode::Triangles => {
+            let mut indices = indices;
+            indices.truncate(indices.len() - indices.len() % 3);
+            Some(indices)
+        }
+        // Every other triangle of a strip is flipped to keep the winding order consistent.
+        Mode::TriangleStrip => Some(
+            indices
+                .windows(3)
+                .enumerate()
+                .flat_map(|(index, window)| match index % 2 {
+                    0 => [window[0], window[1], window[2]],
+                    _ => [window[1], window[0], window[2]],
+                })
+                .collect(),
+        ),
+        Mode::TriangleFan => Some(
+            indices
+                .windows(2)
+                .skip(1)
+                .flat_map(|window| [indices[0], window[0], window[1]])
+                .collect(),
+        ),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => None,
+    }
+}
+
+/// The distance between the original and the reduced skinning of a vertex, relative to the size
+/// of the meshes, above which the vertex is reported.
+const SKINNING_ERROR_THRESHOLD: f32 = 0.01;
+
+/// Returns a warning with the vertices that deform noticeably differently with a single joint,
+/// if there are any.
+fn skinning_warning(scene: &Scene, report: &SkinningReport) -> Option<String> {
+    const MAX_REPORTED_VERTICES: usize = 5;
+
+    let (min, max) = scene.meshes.iter().flat_map(|mesh| &mesh.vertices).fold(
+        (Vec3A::splat(f32::MAX), Vec3A::splat(f32::MIN)),
+        |(min, max), vertex| (min.min(vertex.position), max.max(vertex.position)),
+    );
+    let threshold = SKINNING_ERROR_THRESHOLD * (max - min).length();
+
+    let worst = report.worst_vertices(threshold);
+    if worst.is_empty() {
+        return None;
+    }
+
+    let mut warning = format!(
+        "{} vertices will deform differently in-game because they are influenced by several \
+        joints ({} helper joints, {} split triangles). The largest deviations are:",
+        worst.len(),
+        report.num_helper_joints,
+        report.num_split_triangles,
+    );
+    for &(mesh, vertex, error) in worst.iter().take(MAX_REPORTED_VERTICES) {
+        let position = scene.meshes[mesh].vertices[vertex].position;
+        warning += &format!(
+            "\n  \"{}\", vertex {} at ({:.3}, {:.3}, {:.3}): {:.3}",
+            scene.meshes[mesh].name, vertex, position.x, position.y, position.z, error
+        );
+    }
+    Some(warning)
 }
 
 // Adapted from https://github.com/bevyengine/bevy/blob/c6fec1f0c256597af9746050dd1a4dcd3b80fe24/crates/bevy_gltf/src/loader.rs#L643
-fn load_buffers(gltf: &gltf::Gltf, asset_path: &Path) -> Result<Vec<Vec<u8>>> {
+fn load_buffers(
+    gltf: &gltf::Gltf,
+    asset_path: &Path,
+    compressed_views: &CompressedViews,
+) -> Result<Vec<Vec<u8>>> {
     const VALID_MIME_TYPES: &[&str] = &["application/octet-stream", "application/gltf-buffer"];
 
     let mut buffer_data = Vec::new();
     for buffer in gltf.buffers() {
-        match buffer.source() {
-            gltf::buffer::Source::Uri(uri) => {
-                let buffer_bytes = match DataUri::parse(uri) {
-                    Ok(data_uri) if VALID_MIME_TYPES.contains(&data_uri.mime_type) => {
-                        data_uri.decode()?
-                    }
-                    Ok(_) => return Err(anyhow::anyhow!("Buffer format unsupported")),
-                    Err(()) => {
-                        let buffer_path = asset_path.parent().unwrap().join(uri);
-                        std::fs::read(buffer_path)?
-                    }
-                };
-                buffer_data.push(buffer_bytes);
+        let buffer_bytes = match buffer.source() {
+            // Fallback buffers without data only receive the decompressed buffer views, and are
+            // allocated when decompressing them.
+            gltf::buffer::Source::Bin if compressed_views.is_fallback(buffer.index()) => {
+                buffer_data.push(Vec::new());
+                continue;
             }
+            gltf::buffer::Source::Uri(uri) => match DataUri::parse(uri) {
+                Ok(data_uri) if VALID_MIME_TYPES.contains(&data_uri.mime_type) => data_uri
+                    .decode()
+                    .with_context(|| format!("Buffer {} is not valid base64", buffer.index()))?,
+                Ok(_) => return Err(anyhow::anyhow!("Buffer format unsupported")),
+                Err(()) => {
+                    let file = uri::decode(uri).unwrap_or_else(|| uri.to_string());
+                    let buffer_path = asset_path.parent().unwrap_or(Path::new("")).join(file);
+                    std::fs::read(&buffer_path).with_context(|| {
+                        format!("Failed to read the buffer \"{}\"", buffer_path.display())
+                    })?
+                }
+            },
             gltf::buffer::Source::Bin => {
                 if let Some(blob) = gltf.blob.as_deref() {
-                    buffer_data.push(blob.into());
+                    blob.into()
                 } else {
                     return Err(anyhow::anyhow!("The GLB binary chunk is missing"));
                 }
             }
+        };
+
+        if buffer_bytes.len() < buffer.length() {
+            return Err(anyhow!(
+                "Buffer {} has {} bytes, but {} were expected",
+                buffer.index(),
+                buffer_bytes.len(),
+                buffer.length()
+            ));
         }
+        buffer_data.push(buffer_bytes);
     }
 
     Ok(buffer_data)
@@ -384,3 +1295,440 @@ fn split_once(input: &str, delimiter: char) -> Option<(&str, &str)> {
     let mut iter = input.splitn(2, delimiter);
     Some((iter.next()?, iter.next()?))
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{conversion::Exporter, formats::GltfExporter};
+
+    use super::*;
+
+    #[test]
+    fn sample_linear() {
+        // Keyframes at 30 FPS.
+        let sampler = Sampler::new(
+            vec![0., 1. / 30., 2. / 30.],
+            vec![Vec3::ZERO, Vec3::ONE, Vec3::ZERO],
+            Interpolation::Linear,
+        )
+        .unwrap();
+
+        assert_eq!(Vec3::ZERO, sampler.sample(-1.));
+        assert_eq!(Vec3::ONE, sampler.sample(1. / 30.));
+        assert!(sampler.sample(1. / 60.).abs_diff_eq(Vec3::splat(0.5), 1e-5));
+        assert!(sampler.sample(1. / 20.).abs_diff_eq(Vec3::splat(0.5), 1e-5));
+        assert_eq!(Vec3::ZERO, sampler.sample(1.));
+    }
+
+    #[test]
+    fn sample_step() {
+        let sampler = Sampler::new(
+            vec![0., 1.],
+            vec![Vec3::ZERO, Vec3::ONE],
+            Interpolation::Step,
+        )
+        .unwrap();
+
+        assert_eq!(Vec3::ZERO, sampler.sample(0.99));
+        assert_eq!(Vec3::ONE, sampler.sample(1.));
+    }
+
+    #[test]
+    fn sample_cubic_spline() {
+        // With zero tangents, the midpoint of the curve is halfway between the values.
+        let sampler = Sampler::new(
+            vec![0., 1.],
+            vec![
+                Vec3::ZERO,
+                Vec3::ZERO,
+                Vec3::ZERO,
+                Vec3::ZERO,
+                Vec3::splat(2.),
+                Vec3::ZERO,
+            ],
+            Interpolation::CubicSpline,
+        )
+        .unwrap();
+
+        assert_eq!(Vec3::ZERO, sampler.sample(0.));
+        assert_eq!(Vec3::ONE, sampler.sample(0.5));
+        assert_eq!(Vec3::splat(2.), sampler.sample(1.));
+    }
+
+    #[test]
+    fn sample_rotation() {
+        let sampler = Sampler::new(
+            vec![0., 1.],
+            vec![
+                Quat::IDENTITY,
+                Quat::from_rotation_y(std::f32::consts::PI / 2.),
+            ],
+            Interpolation::Linear,
+        )
+        .unwrap();
+
+        let expected = Quat::from_rotation_y(std::f32::consts::PI / 4.);
+        assert!(sampler.sample(0.5).abs_diff_eq(expected, 1e-5));
+    }
+
+    #[test]
+    fn sampler_mismatched_values() {
+        let sampler = Sampler::new(
+            vec![0., 1.],
+            vec![Vec3::ZERO, Vec3::ONE],
+            Interpolation::CubicSpline,
+        );
+
+        assert!(sampler.is_none());
+    }
+
+    fn linear(times: &[f32]) -> Timeline<'_> {
+        Timeline {
+            times,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    #[test]
+    fn sampling_rate_regular() {
+        let root = linear(&[0., 1. / 30., 2. / 30., 3. / 30.]);
+        let joint = linear(&[1. / 30., 2. / 30.]);
+        let constant = linear(&[0.]);
+
+        assert_eq!(
+            Some(30.),
+            detect_sampling_rate(&[root, joint, constant], 0.)
+        );
+    }
+
+    #[test]
+    fn sampling_rate_irregular() {
+        assert_eq!(None, detect_sampling_rate(&[linear(&[0., 0.1, 0.25])], 0.));
+        assert_eq!(None, detect_sampling_rate(&[linear(&[0.])], 0.));
+        assert_eq!(None, detect_sampling_rate(&[linear(&[0., 2.])], 0.));
+    }
+
+    #[test]
+    fn sampling_rate_sparse() {
+        // Keyframes on a grid with gaps, such as the ones left by keyframe reduction.
+        let root = linear(&[0., 2. / 55., 5. / 55., 9. / 55.]);
+        let joint = linear(&[0., 1. / 55.]);
+        assert_eq!(None, detect_sampling_rate(&[root, joint], 0.));
+
+        let root = linear(&[0., 0.5, 1.]);
+        let step = Timeline {
+            times: &[0., 0.5, 1.],
+            interpolation: Interpolation::Step,
+        };
+        assert_eq!(Some(2.), detect_sampling_rate(&[root], 0.));
+        assert_eq!(None, detect_sampling_rate(&[step], 0.));
+    }
+
+    #[test]
+    fn joint_translations_round_trip() {
+        let transform = Mat4::from_scale_rotation_translation(
+            Vec3::new(1., 2., 1.),
+            Quat::from_rotation_x(1.),
+            Vec3::new(0., 0.5, 0.),
+        );
+        let scene = Scene {
+            meshes: Vec::new(),
+            skeleton: vec![
+                Joint {
+                    translation: Vec3A::new(0., 1., 0.),
+                    rotation: Quat::IDENTITY,
+                    parent: None,
+                    children: vec![1],
+                },
+                Joint {
+                    translation: Vec3A::new(0., 2., 0.),
+                    rotation: Quat::IDENTITY,
+                    parent: Some(0),
+                    children: Vec::new(),
+                },
+            ],
+            animations: vec![Animation {
+                name: String::from("stretch"),
+                sampling_rate: 55.,
+                frames: vec![
+                    Keyframe {
+                        translation: Vec3A::ZERO,
+                        root_motion: Vec3A::ZERO,
+                        option: 0,
+                        transforms: vec![Mat4::IDENTITY, Mat4::IDENTITY],
+                    },
+                    Keyframe {
+                        translation: Vec3A::new(1., 0., 0.),
+                        root_motion: Vec3A::ZERO,
+                        option: 0,
+                        transforms: vec![Mat4::IDENTITY, transform],
+                    },
+                ],
+            }],
+        };
+
+        let (assets, _) = GltfExporter::default().export(&scene).unwrap();
+        let mut actual = Scene::default();
+        GltfImporter::default()
+            .import(&assets[0], &mut actual)
+            .unwrap();
+
+        let animation = &actual.animations[0];
+        assert_eq!(2, animation.frames.len());
+        assert_eq!(Vec3A::new(1., 0., 0.), animation.frames[1].translation);
+        assert_eq!(Mat4::IDENTITY, animation.frames[1].transforms[0]);
+        assert!(animation.frames[1].transforms[1].abs_diff_eq(transform, 1e-5));
+    }
+
+    #[test]
+    fn joint_map_modes() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "nodes": [
+                { "name": "Armature", "children": [1] },
+                { "name": "Hips", "children": [2] },
+                { "name": "Spine" }
+            ],
+            "skins": [{ "joints": [1, 2, 0] }]
+        }"#;
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+
+        let mapping = JointMapping::SkinOrder;
+        let root = get_skeleton_root_index(&gltf, &mapping);
+        assert_eq!(None, root);
+        let expected = HashMap::from([(1, 0), (2, 1), (0, 2)]);
+        assert_eq!(expected, make_joint_map(&gltf, &mapping, root).unwrap());
+
+        let mapping = JointMapping::Names {
+            joints: HashMap::from([(String::from("Hips"), 0), (String::from("Spine"), 1)]),
+            root: Some(String::from("Armature")),
+        };
+        let root = get_skeleton_root_index(&gltf, &mapping);
+        assert_eq!(Some(0), root);
+        let expected = HashMap::from([(1, 0), (2, 1)]);
+        assert_eq!(expected, make_joint_map(&gltf, &mapping, root).unwrap());
+
+        let error = make_joint_map(&gltf, &JointMapping::BoneNames, root).unwrap_err();
+        assert!(error.to_string().contains("\"Hips\", \"Spine\""));
+    }
+
+    #[test]
+    fn multiple_skins() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "nodes": [
+                { "name": "Hips", "children": [1] },
+                { "name": "Hand", "children": [4] },
+                { "name": "Pet", "children": [3] },
+                { "name": "Tail" },
+                { "name": "Sword" }
+            ],
+            "skins": [
+                { "joints": [0, 1] },
+                { "joints": [2, 3], "name": "pet" },
+                { "joints": [4, 1] }
+            ]
+        }"#;
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+
+        let expected = HashMap::from([(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]);
+        let actual = make_joint_map(&gltf, &JointMapping::SkinOrder, None).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(vec![1], find_disjoint_skins(&gltf));
+        assert_eq!("\"pet\"", skin_name(&gltf, 1));
+
+        let joints = convert_joints(&gltf, &actual);
+        assert_eq!(Some(1), joints[4].parent);
+        assert_eq!(None, joints[2].parent);
+    }
+
+    #[test]
+    fn mesh_node_transforms() {
+        let mut buffer = Vec::new();
+        for value in [0., 0., 0., 1., 0., 0., 0., 1., 0.] {
+            buffer.extend_from_slice(&f32::to_le_bytes(value));
+        }
+        buffer.extend_from_slice(&[0; 12]);
+        for value in [1., 0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 0.] {
+            buffer.extend_from_slice(&f32::to_le_bytes(value));
+        }
+        for value in Mat4::IDENTITY.to_cols_array() {
+            buffer.extend_from_slice(&f32::to_le_bytes(value));
+        }
+
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0, 3, 4] }}],
+                "nodes": [
+                    {{ "translation": [0, 1, 0], "children": [1, 2] }},
+                    {{ "mesh": 0, "translation": [1, 0, 0], "scale": [2, 2, 2] }},
+                    {{ "mesh": 0, "scale": [-1, 1, 1] }},
+                    {{ "name": "bone_0", "translation": [0, 0, 5] }},
+                    {{ "mesh": 1, "skin": 0 }}
+                ],
+                "skins": [{{ "joints": [3], "inverseBindMatrices": 3 }}],
+                "meshes": [
+                    {{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }},
+                    {{
+                        "primitives": [{{
+                            "attributes": {{ "POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2 }}
+                        }}]
+                    }}
+                ],
+                "accessors": [
+                    {{
+                        "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                        "min": [0, 0, 0], "max": [1, 1, 0]
+                    }},
+                    {{ "bufferView": 1, "componentType": 5121, "count": 3, "type": "VEC4" }},
+                    {{ "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" }},
+                    {{ "bufferView": 3, "componentType": 5126, "count": 1, "type": "MAT4" }}
+                ],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 12 }},
+                    {{ "buffer": 0, "byteOffset": 48, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 96, "byteLength": 64 }}
+                ],
+                "buffers": [{{
+                    "byteLength": {},
+                    "uri": "data:application/octet-stream;base64,{}"
+                }}]
+            }}"#,
+            buffer.len(),
+            base64::encode(&buffer)
+        );
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+        let buffers = load_buffers(&gltf, Path::new(""), &CompressedViews::default()).unwrap();
+        let joint_map = HashMap::from([(3, 0)]);
+        let bind_transforms = [Mat4::from_translation(Vec3::new(0., 0., 5.))];
+
+        let (meshes, influences) = convert_meshes(
+            &gltf,
+            &buffers,
+            &joint_map,
+            &bind_transforms,
+            false,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        let positions = |mesh: &Mesh| -> Vec<_> {
+            mesh.vertices.iter().map(|vertex| vertex.position).collect()
+        };
+        assert_eq!(3, meshes.len());
+        assert_eq!(
+            vec![
+                Vec3A::new(1., 1., 0.),
+                Vec3A::new(3., 1., 0.),
+                Vec3A::new(1., 3., 0.)
+            ],
+            positions(&meshes[0])
+        );
+        assert_eq!(
+            vec![
+                Vec3A::new(0., 1., 0.),
+                Vec3A::new(-1., 1., 0.),
+                Vec3A::new(0., 2., 0.)
+            ],
+            positions(&meshes[1])
+        );
+        assert_eq!(
+            vec![
+                Vec3A::new(0., 0., 5.),
+                Vec3A::new(1., 0., 5.),
+                Vec3A::new(0., 1., 5.)
+            ],
+            positions(&meshes[2])
+        );
+        assert_eq!(vec![(0, 1.)], influences[2][0]);
+    }
+
+    #[test]
+    fn triangulate_modes() {
+        use gltf::mesh::Mode;
+
+        let indices = vec![0, 1, 2, 3, 4];
+        assert_eq!(
+            Some(vec![0, 1, 2]),
+            triangulate(Mode::Triangles, indices.clone())
+        );
+        assert_eq!(
+            Some(vec![0, 1, 2, 2, 1, 3, 2, 3, 4]),
+            triangulate(Mode::TriangleStrip, indices.clone())
+        );
+        assert_eq!(
+            Some(vec![0, 1, 2, 0, 2, 3, 0, 3, 4]),
+            triangulate(Mode::TriangleFan, indices.clone())
+        );
+        assert_eq!(None, triangulate(Mode::LineStrip, indices));
+        assert_eq!(Some(Vec::new()), triangulate(Mode::TriangleFan, Vec::new()));
+    }
+
+    #[test]
+    fn merge_primitives() {
+        let primitive = |name: &str, position: Vec3A, joint: usize| {
+            let vertex = Vertex {
+                position,
+                normal: Vec3A::Z,
+                uv: Vec2::ZERO,
+                joint: None,
+            };
+            let mesh = Mesh {
+                name: name.into(),
+                vertices: vec![vertex.clone(); 3],
+                indices: vec![0, 2, 1],
+            };
+            (mesh, vec![vec![(joint, 1.)]; 3])
+        };
+
+        let (mesh, influences) = merge_meshes(vec![
+            primitive("body", Vec3A::X, 0),
+            primitive("body", Vec3A::Y, 1),
+        ]);
+        assert_eq!("body", mesh.name);
+        assert_eq!(6, mesh.vertices.len());
+        assert_eq!(Vec3A::Y, mesh.vertices[3].position);
+        assert_eq!(vec![0, 2, 1, 3, 5, 4], mesh.indices);
+        assert_eq!(vec![(1, 1.)], influences[3]);
+    }
+
+    #[test]
+    fn profile_round_trip() {
+        let profile = SkeletonProfile::parse("hero", "bone_0 = Spine\nbone_1 = Head").unwrap();
+        let scene = Scene {
+            meshes: Vec::new(),
+            skeleton: vec![
+                Joint {
+                    translation: Vec3A::new(0., 1., 0.),
+                    children: vec![1],
+                    ..Default::default()
+                },
+                Joint {
+                    translation: Vec3A::new(0., 2., 0.),
+                    parent: Some(0),
+                    ..Default::default()
+                },
+            ],
+            animations: Vec::new(),
+        };
+
+        let exporter = GltfExporter {
+            profiles: vec![profile.clone()],
+            ..Default::default()
+        };
+        let (assets, _) = exporter.export(&scene).unwrap();
+        let gltf = gltf::Gltf::from_slice(&assets[0].bytes).unwrap();
+        assert_eq!(Some("Head"), gltf.nodes().nth(1).unwrap().name());
+
+        let importer = GltfImporter {
+            profiles: vec![profile],
+            ..Default::default()
+        };
+        let mut actual = Scene::default();
+        importer.import(&assets[0], &mut actual).unwrap();
+        assert_eq!(scene.skeleton, actual.skeleton);
+    }
+}
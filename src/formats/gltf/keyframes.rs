@@ -0,0 +1,307 @@
+use std::ops::{Add, Mul, Sub};
+
+use glam::{Mat3, Mat4, Quat, Vec3, Vec4};
+
+/// A joint matrix split into the components of a glTF node transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decomposition {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    /// The largest cosine of the angle between two axes of the matrix, which is zero if the
+    /// axes are perpendicular. Shear can't be stored in glTF nodes, so it's discarded.
+    pub shear: f32,
+}
+
+/// Splits the matrix into a translation, a rotation and a scale. Unlike
+/// [`Mat4::to_scale_rotation_translation`], the rotation is extracted from the nearest
+/// orthonormal matrix, so that matrices with rounding errors or shear still produce valid
+/// rotations.
+pub fn decompose(matrix: &Mat4) -> Decomposition {
+    let mut axes = [
+        matrix.x_axis.truncate(),
+        matrix.y_axis.truncate(),
+        matrix.z_axis.truncate(),
+    ];
+    let mut scale = Vec3::from(axes.map(Vec3::length));
+    // A mirrored matrix is represented with a negative scale along the X axis.
+    if matrix.determinant() < 0. {
+        scale.x = -scale.x;
+    }
+    for (axis, &scale) in axes.iter_mut().zip(scale.as_ref()) {
+        if scale != 0. {
+            *axis /= scale;
+        }
+    }
+
+    let shear = [(0, 1), (0, 2), (1, 2)]
+        .into_iter()
+        .map(|(a, b)| axes[a].dot(axes[b]).abs())
+        .fold(0., f32::max);
+    let rotation = orthonormalize(Mat3::from_cols(axes[0], axes[1], axes[2]))
+        .map(|rotation| Quat::from_mat3(&rotation).normalize())
+        .unwrap_or_default();
+
+    Decomposition {
+        translation: matrix.w_axis.truncate(),
+        rotation,
+        scale,
+        shear,
+    }
+}
+
+/// Returns the rotation matrix nearest to the given matrix, which is found by averaging it with
+/// its inverse transpose until both converge. Returns `None` if the matrix is degenerate.
+fn orthonormalize(mut matrix: Mat3) -> Option<Mat3> {
+    const MAX_ITERATIONS: usize = 16;
+
+    for _ in 0..MAX_ITERATIONS {
+        let determinant = matrix.determinant();
+        if !determinant.is_finite() || determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let next = (matrix + matrix.inverse().transpose()) * 0.5;
+        let converged = next.abs_diff_eq(matrix, 1e-7);
+        matrix = next;
+        if converged {
+            break;
+        }
+    }
+
+    Some(matrix)
+}
+
+/// Returns the frames needed to interpolate the rotations with an error of at most `max_angle`
+/// radians. The rotations are interpolated linearly, or with a cubic spline if their tangents
+/// are given.
+pub fn reduce_rotations(
+    rotations: &[Quat],
+    tangents: Option<&[Quat]>,
+    max_angle: f32,
+) -> Vec<usize> {
+    let interpolate = |start: usize, end: usize, t: f32| match tangents {
+        Some(tangents) => Quat::from_vec4(hermite(
+            Vec4::from(rotations[start]),
+            Vec4::from(tangents[start]),
+            Vec4::from(rotations[end]),
+            Vec4::from(tangents[end]),
+            (end - start) as f32,
+            t,
+        ))
+        .normalize(),
+        None => rotations[start].slerp(rotations[end], t),
+    };
+    reduce(rotations, max_angle, interpolate, Quat::angle_between)
+}
+
+/// Returns the frames needed to interpolate the vectors with an error of at most
+/// `max_distance`. The vectors are interpolated linearly, or with a cubic spline if their
+/// tangents are given.
+pub fn reduce_vectors(
+    vectors: &[Vec3],
+    tangents: Option<&[Vec3]>,
+    max_distance: f32,
+) -> Vec<usize> {
+    let interpolate = |start: usize, end: usize, t: f32| match tangents {
+        Some(tangents) => hermite(
+            vectors[start],
+            tangents[start],
+            vectors[end],
+            tangents[end],
+            (end - start) as f32,
+            t,
+        ),
+        None => vectors[start].lerp(vectors[end], t),
+    };
+    reduce(vectors, max_distance, interpolate, Vec3::distance)
+}
+
+/// Returns the tangent of each rotation per frame, which is estimated from its neighbouring
+/// frames. The rotations must not change hemisphere between frames.
+pub fn rotation_tangents(rotations: &[Quat]) -> Vec<Quat> {
+    let rotations: Vec<_> = rotations.iter().copied().map(Vec4::from).collect();
+    tangents(&rotations)
+        .into_iter()
+        .map(Quat::from_vec4)
+        .collect()
+}
+
+/// Returns the tangent of each vector per frame, which is estimated from its neighbouring
+/// frames.
+pub fn vector_tangents(vectors: &[Vec3]) -> Vec<Vec3> {
+    tangents(vectors)
+}
+
+/// Flips the sign of the rotations that are in the opposite hemisphere of the previous one.
+/// Both signs represent the same rotation, but viewers that interpolate the components of the
+/// quaternions would spin the joint the long way around between them.
+pub fn align_hemispheres(rotations: &mut [Quat]) {
+    for i in 1..rotations.len() {
+        if rotations[i].dot(rotations[i - 1]) < 0. {
+            rotations[i] = -rotations[i];
+        }
+    }
+}
+
+/// Estimates the tangents with the central difference of the neighbouring frames, or the
+/// one-sided difference at both ends.
+fn tangents<T>(values: &[T]) -> Vec<T>
+where
+    T: Copy + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    (0..values.len())
+        .map(|frame| {
+            let prev = frame.saturating_sub(1);
+            let next = (frame + 1).min(values.len() - 1);
+            (values[next] - values[prev]) * (1. / (next - prev).max(1) as f32)
+        })
+        .collect()
+}
+
+/// Evaluates the cubic Hermite spline between two values at `t`, with tangents given per frame
+/// and the values `frames` frames apart.
+fn hermite<T>(start: T, start_tangent: T, end: T, end_tangent: T, frames: f32, t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    start * (2. * t3 - 3. * t2 + 1.)
+        + start_tangent * ((t3 - 2. * t2 + t) * frames)
+        + end * (-2. * t3 + 3. * t2)
+        + end_tangent * ((t3 - t2) * frames)
+}
+
+/// Returns the frames needed to interpolate the evenly spaced values within the tolerance. The
+/// first and last frames are always kept, so that the duration of the animation is preserved.
+///
+/// Each kept frame is followed by the longest run of frames that can be interpolated between
+/// the kept frame and the frame after the run. `interpolate` receives the indices of both
+/// frames and the position between them.
+fn reduce<T: Copy>(
+    values: &[T],
+    tolerance: f32,
+    interpolate: impl Fn(usize, usize, f32) -> T,
+    error: impl Fn(T, T) -> f32,
+) -> Vec<usize> {
+    if values.len() <= 2 {
+        return (0..values.len()).collect();
+    }
+
+    let mut frames = vec![0];
+    let mut start = 0;
+    let mut end = 2;
+    while end < values.len() {
+        let fits = (start + 1..end).all(|frame| {
+            let t = (frame - start) as f32 / (end - start) as f32;
+            error(interpolate(start, end, t), values[frame]) <= tolerance
+        });
+        if fits {
+            end += 1;
+        } else {
+            start = end - 1;
+            frames.push(start);
+            end = start + 2;
+        }
+    }
+    frames.push(values.len() - 1);
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn vectors() {
+        assert_eq!(vec![0, 4], reduce_vectors(&[Vec3::ONE; 5], None, 1e-3));
+
+        let ramp: Vec<_> = (0..10).map(|i| Vec3::X * i as f32).collect();
+        assert_eq!(vec![0, 9], reduce_vectors(&ramp, None, 1e-3));
+
+        let corner: Vec<_> = (0..10)
+            .map(|i| Vec3::new(i as f32, (i as f32 - 5.).max(0.), 0.))
+            .collect();
+        assert_eq!(vec![0, 5, 9], reduce_vectors(&corner, None, 1e-3));
+        assert_eq!(vec![0, 9], reduce_vectors(&corner, None, 10.));
+
+        assert_eq!(vec![0], reduce_vectors(&[Vec3::ZERO], None, 1e-3));
+        assert!(reduce_vectors(&[], None, 1e-3).is_empty());
+    }
+
+    #[test]
+    fn rotations() {
+        let spin: Vec<_> = (0..10)
+            .map(|i| Quat::from_rotation_z(i as f32 * 0.1))
+            .collect();
+        assert_eq!(vec![0, 9], reduce_rotations(&spin, None, 1e-3));
+
+        let swing: Vec<_> = (0..10)
+            .map(|i| Quat::from_rotation_z((i as f32 - 5.).abs() * 0.1))
+            .collect();
+        assert_eq!(vec![0, 5, 9], reduce_rotations(&swing, None, 1e-3));
+    }
+
+    #[test]
+    fn cubic_spline() {
+        let curve: Vec<_> = (0..20)
+            .map(|i| Vec3::new(i as f32, (i as f32 * 0.2).sin(), 0.))
+            .collect();
+        let tangents = vector_tangents(&curve);
+        assert_eq!(Vec3::X, tangents[5].round());
+        let linear = reduce_vectors(&curve, None, 1e-2);
+        let cubic = reduce_vectors(&curve, Some(&tangents), 1e-2);
+        assert_eq!(0, cubic[0]);
+        assert_eq!(19, cubic[cubic.len() - 1]);
+        assert!(cubic.len() < linear.len());
+
+        let mut swing: Vec<_> = (0..10)
+            .map(|i| Quat::from_rotation_z(i as f32 * 0.3))
+            .collect();
+        swing[3] = -swing[3];
+        align_hemispheres(&mut swing);
+        assert!(swing.windows(2).all(|pair| pair[0].dot(pair[1]) > 0.));
+        let tangents = rotation_tangents(&swing);
+        assert_eq!(
+            vec![0, 6, 9],
+            reduce_rotations(&swing, Some(&tangents), 1e-2)
+        );
+    }
+
+    #[test]
+    fn decomposition() {
+        let rotation = Quat::from_euler(glam::EulerRot::XYZ, 0.3, -1.2, 2.);
+        let matrix = Mat4::from_scale_rotation_translation(
+            Vec3::new(1., 2., 3.),
+            rotation,
+            Vec3::new(4., 5., 6.),
+        );
+        let actual = decompose(&matrix);
+        assert_eq!(Vec3::new(4., 5., 6.), actual.translation);
+        assert!(
+            actual.rotation.abs_diff_eq(rotation, 1e-5)
+                || actual.rotation.abs_diff_eq(-rotation, 1e-5)
+        );
+        assert!(actual.scale.abs_diff_eq(Vec3::new(1., 2., 3.), 1e-5));
+        assert!(actual.shear < 1e-5);
+
+        // Rotation matrices written with few decimals are not quite orthonormal.
+        let mut sheared = Mat4::from_quat(rotation);
+        sheared.y_axis += sheared.x_axis * 0.01;
+        let actual = decompose(&sheared);
+        assert!(actual.rotation.is_normalized());
+        assert!(actual.rotation.angle_between(rotation) < 0.01);
+        assert!(actual.shear > 5e-3);
+
+        let mirrored = Mat4::from_scale(Vec3::new(1., -1., 1.));
+        let actual = decompose(&mirrored);
+        assert_eq!(Vec3::new(-1., 1., 1.), actual.scale);
+        assert!(actual.rotation.is_normalized());
+
+        assert_eq!(Quat::IDENTITY, decompose(&Mat4::ZERO).rotation);
+    }
+}
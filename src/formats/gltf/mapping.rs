@@ -0,0 +1,212 @@
+use std::{collections::HashMap, fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::profile::{parse_joint, SkeletonProfile};
+
+/// The file extension of joint mapping files.
+pub const MAPPING_EXTENSION: &str = "bonemap";
+
+/// Specifies how the nodes of a glTF skeleton are mapped to the joints of the scene.
+///
+/// A mapping can be loaded from a text file with one `node = joint` pair per line, where `joint`
+/// is either `bone_N` (or just `N`), being N the index of the joint, or `root` for the node that
+/// moves the whole skeleton. The `joint = node` order of [`SkeletonProfile`] files is accepted
+/// too. Alternatively, the file can contain the single directive `skin_order`. Lines starting
+/// with `#` are ignored. For example:
+///
+/// ```text
+/// # Mixamo rig
+/// Armature = root
+/// mixamorig:Hips = bone_0
+/// mixamorig:Spine = 1
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum JointMapping {
+    /// Joints are named "bone_N", where N is the index of the joint. The root of the skeleton
+    /// is named "root".
+    #[default]
+    BoneNames,
+    /// Joints are indexed by their order in the skin. The root of the skeleton is named "root".
+    SkinOrder,
+    /// Joints and the root of the skeleton are mapped by node name.
+    Names {
+        /// The index of the joint of each node name.
+        joints: HashMap<String, usize>,
+        /// The name of the root node of the skeleton. If it's not specified, the node named
+        /// "root" is used.
+        root: Option<String>,
+    },
+}
+
+impl JointMapping {
+    /// Reads a joint mapping from a text file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        text.parse()
+            .with_context(|| format!("Invalid joint mapping file \"{}\"", path.display()))
+    }
+
+    /// Returns the name of the root node of the skeleton.
+    pub fn root_name(&self) -> &str {
+        match self {
+            JointMapping::Names {
+                root: Some(root), ..
+            } => root,
+            _ => "root",
+        }
+    }
+}
+
+impl From<&SkeletonProfile> for JointMapping {
+    fn from(profile: &SkeletonProfile) -> Self {
+        JointMapping::Names {
+            joints: profile
+                .joint_names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| (name.clone(), index))
+                .collect(),
+            root: None,
+        }
+    }
+}
+
+impl FromStr for JointMapping {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let mut joints = HashMap::new();
+        let mut root = None;
+        let mut skin_order = false;
+        for (number, line) in lines {
+            if line == "skin_order" {
+                skin_order = true;
+                continue;
+            }
+
+            let (left, right) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Line {}: expected \"node = bone_N\"", number))?;
+            let (left, right) = (left.trim(), right.trim());
+            let (node, joint) = match (parse_target(left, false), parse_target(right, true)) {
+                (None, Some(joint)) => (left, joint),
+                (Some(joint), None) => (right, joint),
+                (Some(_), Some(_)) => {
+                    return Err(anyhow!(
+                        "Line {}: both \"{}\" and \"{}\" are joints",
+                        number,
+                        left,
+                        right
+                    ))
+                }
+                (None, None) => {
+                    return Err(anyhow!(
+                        "Line {}: \"{}\" is not a valid joint",
+                        number,
+                        right
+                    ))
+                }
+            };
+            if node.is_empty() {
+                return Err(anyhow!("Line {}: the node name is empty", number));
+            }
+
+            match joint {
+                Target::Root => root = Some(node.to_string()),
+                Target::Joint(index) => {
+                    if joints.insert(node.to_string(), index).is_some() {
+                        return Err(anyhow!("Line {}: \"{}\" is mapped twice", number, node));
+                    }
+                }
+            }
+        }
+
+        if skin_order {
+            if !joints.is_empty() || root.is_some() {
+                return Err(anyhow!(
+                    "The \"skin_order\" directive cannot be combined with node names"
+                ));
+            }
+            Ok(JointMapping::SkinOrder)
+        } else {
+            Ok(JointMapping::Names { joints, root })
+        }
+    }
+}
+
+/// What a node is mapped to.
+enum Target {
+    Root,
+    Joint(usize),
+}
+
+/// Parses `root` or a joint written as "bone_N". A bare index is only accepted if `bare_index`
+/// is set, since node names can be numbers too.
+fn parse_target(text: &str, bare_index: bool) -> Option<Target> {
+    if text == "root" {
+        return Some(Target::Root);
+    }
+    parse_joint(text)
+        .or_else(|| bare_index.then(|| text.parse().ok()).flatten())
+        .map(Target::Joint)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        let text = "# Comment\nroot = Armature\n\nbone_0 = Hips\n  bone_1=Spine\n";
+        let expected = JointMapping::Names {
+            joints: HashMap::from([(String::from("Hips"), 0), (String::from("Spine"), 1)]),
+            root: Some(String::from("Armature")),
+        };
+
+        assert_eq!(expected, text.parse().unwrap());
+        assert_eq!("Armature", expected.root_name());
+    }
+
+    #[test]
+    fn parse_node_first() {
+        let text = "Armature = root\nHips=bone_0\nSpine = 1\nbone_2 = Neck\n";
+        let expected = JointMapping::Names {
+            joints: HashMap::from([
+                (String::from("Hips"), 0),
+                (String::from("Spine"), 1),
+                (String::from("Neck"), 2),
+            ]),
+            root: Some(String::from("Armature")),
+        };
+
+        assert_eq!(expected, text.parse().unwrap());
+    }
+
+    #[test]
+    fn parse_skin_order() {
+        assert_eq!(JointMapping::SkinOrder, "skin_order".parse().unwrap());
+        assert!("skin_order\nbone_0 = Hips".parse::<JointMapping>().is_err());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!("Hips".parse::<JointMapping>().is_err());
+        assert!("bone_x = Hips".parse::<JointMapping>().is_err());
+        assert!("Hips = Spine".parse::<JointMapping>().is_err());
+        assert!("bone_0 = bone_1".parse::<JointMapping>().is_err());
+        assert!("root = bone_0".parse::<JointMapping>().is_err());
+        assert!("= bone_0".parse::<JointMapping>().is_err());
+        assert!("bone_0 = Hips\nbone_1 = Hips"
+            .parse::<JointMapping>()
+            .is_err());
+    }
+}
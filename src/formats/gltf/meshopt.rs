@@ -0,0 +1,1044 @@
+use std::{collections::HashSet, ops::Range};
+
+use anyhow::{anyhow, Context, Result};
+use gltf::json::Value;
+
+/// The name of the glTF extension that compresses buffer views.
+pub const EXTENSION_NAME: &str = "EXT_meshopt_compression";
+
+const VERTEX_HEADER: u8 = 0xa0;
+const INDEX_HEADER: u8 = 0xe0;
+const SEQUENCE_HEADER: u8 = 0xd0;
+
+const BYTE_GROUP_SIZE: usize = 16;
+const VERTEX_BLOCK_SIZE_BYTES: usize = 8192;
+const VERTEX_BLOCK_MAX_SIZE: usize = 256;
+const TAIL_MAX_SIZE: usize = 32;
+
+/// The combinations of vertex FIFO positions that can be stored in the triangle code. The table
+/// is also written at the end of encoded index buffers.
+const CODEAUX_TABLE: [u8; 16] = [
+    0x00, 0x76, 0x87, 0x56, 0x67, 0x78, 0xa9, 0x86, 0x65, 0x89, 0x68, 0x98, 0x01, 0x69, 0x00, 0x00,
+];
+
+/// Specifies how the elements of a compressed buffer view are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Vertex attributes.
+    Attributes,
+    /// Triangle list indices.
+    Triangles,
+    /// Other indices, such as those of sparse accessors.
+    Indices,
+}
+
+/// Specifies how the decoded attributes are transformed into their final values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    None,
+    /// Unit vectors stored as octahedral coordinates.
+    Octahedral,
+    /// Unit quaternions stored as their three smallest components.
+    Quaternion,
+    /// Floats stored as a shared exponent and a 24-bit mantissa.
+    Exponential,
+}
+
+/// A buffer view compressed with `EXT_meshopt_compression`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CompressedView {
+    /// The buffer view that receives the decompressed data.
+    view: usize,
+    target_buffer: usize,
+    target_offset: usize,
+    target_length: usize,
+    /// The compressed data.
+    buffer: usize,
+    offset: usize,
+    length: usize,
+    stride: usize,
+    count: usize,
+    mode: Mode,
+    filter: Filter,
+}
+
+/// The buffer views of a glTF file that are compressed with `EXT_meshopt_compression`.
+///
+/// The decompressed data is written into the buffers of the views, which are usually fallback
+/// buffers that have no data of their own. Fallback buffers are allocated as their views are
+/// decompressed, so that their declared length doesn't need to be trusted.
+#[derive(Debug, Default)]
+pub struct CompressedViews {
+    views: Vec<CompressedView>,
+    fallback_buffers: HashSet<usize>,
+}
+
+impl CompressedViews {
+    /// Reads the compressed buffer views from the JSON document of a glTF file.
+    pub fn parse(json: &Value) -> Result<Self> {
+        let mut result = Self::default();
+
+        let buffers = json["buffers"].as_array().map_or(&[][..], Vec::as_slice);
+        for (index, buffer) in buffers.iter().enumerate() {
+            if buffer["extensions"][EXTENSION_NAME]["fallback"] == Value::Bool(true) {
+                result.fallback_buffers.insert(index);
+            }
+        }
+
+        let views = json["bufferViews"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+        for (index, view) in views.iter().enumerate() {
+            let extension = &view["extensions"][EXTENSION_NAME];
+            if extension.is_null() {
+                continue;
+            }
+            let compressed = parse_view(index, view, extension)
+                .with_context(|| format!("Invalid compressed buffer view {}", index))?;
+            result.views.push(compressed);
+        }
+
+        Ok(result)
+    }
+
+    /// Returns whether the buffer with the given index only exists to hold decompressed data.
+    pub fn is_fallback(&self, buffer: usize) -> bool {
+        self.fallback_buffers.contains(&buffer)
+    }
+
+    /// Decompresses every compressed buffer view into its buffer.
+    pub fn decompress(&self, buffers: &mut [Vec<u8>]) -> Result<()> {
+        let mut decoded_views = Vec::with_capacity(self.views.len());
+        for view in &self.views {
+            let data = buffers
+                .get(view.buffer)
+                .zip(range(view.offset, view.length))
+                .and_then(|(buffer, range)| buffer.get(range))
+                .ok_or_else(|| anyhow!("Compressed buffer view {} is out of bounds", view.view))?;
+            let decoded = decode(data, view.count, view.stride, view.mode, view.filter)
+                .with_context(|| format!("Failed to decompress buffer view {}", view.view))?;
+            decoded_views.push(decoded);
+        }
+
+        // Fallback buffers only hold compressed views, so they can't be larger than the views
+        // with their alignment padding.
+        for &buffer in &self.fallback_buffers {
+            let views = || {
+                self.views
+                    .iter()
+                    .filter(move |view| view.target_buffer == buffer)
+            };
+            let max_length = views()
+                .map(|view| view.target_length.saturating_add(3))
+                .fold(0, usize::saturating_add);
+            let length = views()
+                .map(|view| view.target_offset.saturating_add(view.target_length))
+                .max()
+                .unwrap_or_default();
+            if length > max_length {
+                return Err(anyhow!(
+                    "Fallback buffer {} is larger than its compressed buffer views",
+                    buffer
+                ));
+            }
+            if let Some(buffer) = buffers.get_mut(buffer) {
+                buffer.resize(buffer.len().max(length), 0);
+            }
+        }
+
+        for (view, decoded) in self.views.iter().zip(decoded_views) {
+            let target = buffers
+                .get_mut(view.target_buffer)
+                .zip(range(view.target_offset, view.target_length))
+                .and_then(|(buffer, range)| buffer.get_mut(range))
+                .ok_or_else(|| anyhow!("Buffer view {} is out of bounds", view.view))?;
+            target.copy_from_slice(&decoded);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the range of bytes with the given offset and length, or `None` if it overflows.
+fn range(offset: usize, length: usize) -> Option<Range<usize>> {
+    Some(offset..offset.checked_add(length)?)
+}
+
+fn parse_view(index: usize, view: &Value, extension: &Value) -> Result<CompressedView> {
+    let field = |value: &Value, name: &str| {
+        value[name]
+            .as_u64()
+            .map(|value| value as usize)
+            .ok_or_else(|| anyhow!("\"{}\" is missing or invalid", name))
+    };
+
+    let mode = match extension["mode"].as_str() {
+        Some("ATTRIBUTES") => Mode::Attributes,
+        Some("TRIANGLES") => Mode::Triangles,
+        Some("INDICES") => Mode::Indices,
+        _ => return Err(anyhow!("\"mode\" is missing or invalid")),
+    };
+    let filter = match extension["filter"].as_str() {
+        None | Some("NONE") => Filter::None,
+        Some("OCTAHEDRAL") => Filter::Octahedral,
+        Some("QUATERNION") => Filter::Quaternion,
+        Some("EXPONENTIAL") => Filter::Exponential,
+        Some(filter) => return Err(anyhow!("Unknown filter \"{}\"", filter)),
+    };
+
+    let target_length = field(view, "byteLength")?;
+    let stride = field(extension, "byteStride")?;
+    let count = field(extension, "count")?;
+    if count.checked_mul(stride) != Some(target_length) {
+        return Err(anyhow!(
+            "{} elements of {} bytes don't match the length of the buffer view ({} bytes)",
+            count,
+            stride,
+            target_length
+        ));
+    }
+
+    Ok(CompressedView {
+        view: index,
+        target_buffer: field(view, "buffer")?,
+        target_offset: view["byteOffset"].as_u64().unwrap_or_default() as usize,
+        target_length,
+        buffer: field(extension, "buffer")?,
+        offset: extension["byteOffset"].as_u64().unwrap_or_default() as usize,
+        length: field(extension, "byteLength")?,
+        stride,
+        count,
+        mode,
+        filter,
+    })
+}
+
+/// Decodes `count` elements of `stride` bytes each.
+pub fn decode(
+    data: &[u8],
+    count: usize,
+    stride: usize,
+    mode: Mode,
+    filter: Filter,
+) -> Result<Vec<u8>> {
+    let mut output = match mode {
+        Mode::Attributes => decode_vertex_buffer(data, count, stride)?,
+        Mode::Triangles => write_indices(&decode_index_buffer(data, count)?, stride)?,
+        Mode::Indices => write_indices(&decode_index_sequence(data, count)?, stride)?,
+    };
+
+    match (mode, filter) {
+        (_, Filter::None) => {}
+        (Mode::Attributes, Filter::Octahedral) => decode_octahedral(&mut output, stride)?,
+        (Mode::Attributes, Filter::Quaternion) => decode_quaternion(&mut output, stride)?,
+        (Mode::Attributes, Filter::Exponential) => decode_exponential(&mut output, stride)?,
+        _ => return Err(anyhow!("Filters can only be applied to attributes")),
+    }
+
+    Ok(output)
+}
+
+/// Decodes a vertex buffer: for each block of vertices, the deltas between consecutive values of
+/// each byte are zigzag-encoded and packed in groups of 16 with 0, 2, 4 or 8 bits per value.
+fn decode_vertex_buffer(data: &[u8], count: usize, stride: usize) -> Result<Vec<u8>> {
+    if stride == 0 || stride > 256 || !stride.is_multiple_of(4) {
+        return Err(anyhow!("Invalid vertex stride {}", stride));
+    }
+    check_header(data, VERTEX_HEADER, 0)?;
+
+    // The first vertex is stored at the end of the data, padded to at least 32 bytes.
+    let tail_size = stride.max(TAIL_MAX_SIZE);
+    if data.len() < 1 + tail_size {
+        return Err(truncated());
+    }
+    let mut last_vertex = data[data.len() - stride..].to_vec();
+    let mut reader = Reader::new(&data[1..data.len() - tail_size]);
+
+    // Each byte of each block has at least one header byte, which bounds the number of vertices
+    // before allocating them.
+    let block_size = vertex_block_size(stride);
+    let num_blocks = count.div_ceil(block_size);
+    if num_blocks.saturating_mul(stride) > reader.data.len() {
+        return Err(truncated());
+    }
+    let mut output = vec![0; count * stride];
+    let mut deltas = [0; VERTEX_BLOCK_MAX_SIZE];
+    for block_start in (0..count).step_by(block_size) {
+        let block_count = block_size.min(count - block_start);
+        let aligned_count = block_count.next_multiple_of(BYTE_GROUP_SIZE);
+
+        for (k, last) in last_vertex.iter_mut().enumerate() {
+            decode_bytes(&mut reader, &mut deltas[..aligned_count])?;
+            for (i, &delta) in deltas[..block_count].iter().enumerate() {
+                *last = unzigzag8(delta).wrapping_add(*last);
+                output[(block_start + i) * stride + k] = *last;
+            }
+        }
+    }
+
+    if !reader.data.is_empty() {
+        return Err(anyhow!("Unexpected data after the vertices"));
+    }
+    Ok(output)
+}
+
+fn decode_bytes(reader: &mut Reader, values: &mut [u8]) -> Result<()> {
+    let num_groups = values.len() / BYTE_GROUP_SIZE;
+    let header = reader.take(num_groups.div_ceil(4))?;
+
+    for (group, values) in values.chunks_exact_mut(BYTE_GROUP_SIZE).enumerate() {
+        match (header[group / 4] >> ((group % 4) * 2)) & 3 {
+            0 => values.fill(0),
+            1 => decode_bytes_group(reader, values, 2)?,
+            2 => decode_bytes_group(reader, values, 4)?,
+            _ => values.copy_from_slice(reader.take(BYTE_GROUP_SIZE)?),
+        }
+    }
+    Ok(())
+}
+
+/// Decodes a group of values packed with the given number of bits. Values that don't fit are
+/// marked with all bits set and stored as full bytes after the group.
+fn decode_bytes_group(reader: &mut Reader, values: &mut [u8], bits: usize) -> Result<()> {
+    let packed = reader.take(BYTE_GROUP_SIZE * bits / 8)?;
+    let sentinel = (1 << bits) - 1;
+    let values_per_byte = 8 / bits;
+
+    for (i, value) in values.iter_mut().enumerate() {
+        let shift = 8 - bits * (i % values_per_byte + 1);
+        let encoded = (packed[i / values_per_byte] >> shift) & sentinel;
+        *value = if encoded == sentinel {
+            reader.read_u8()?
+        } else {
+            encoded
+        };
+    }
+    Ok(())
+}
+
+/// Decodes a triangle list. Triangles are encoded relative to a FIFO of recently seen edges and
+/// a FIFO of recently seen vertices, so that most of them take a single byte.
+fn decode_index_buffer(data: &[u8], count: usize) -> Result<Vec<u32>> {
+    if !count.is_multiple_of(3) {
+        return Err(anyhow!("The number of indices is not a multiple of 3"));
+    }
+    let num_triangles = count / 3;
+    if data.len() < 1 + num_triangles + 16 {
+        return Err(truncated());
+    }
+    let version = check_header(data, INDEX_HEADER, 1)?;
+
+    // Version 1 encodes free indices that differ by one from the previous one in the code.
+    let fec_max = if version >= 1 { 13 } else { 15 };
+
+    let codes = &data[1..1 + num_triangles];
+    let codeaux_table = &data[data.len() - 16..];
+    let mut reader = Reader::new(&data[1 + num_triangles..data.len() - 16]);
+
+    let mut edge_fifo = Fifo::new([u32::MAX; 2]);
+    let mut vertex_fifo = Fifo::new(u32::MAX);
+    let mut next = 0u32;
+    let mut last = 0u32;
+
+    let mut indices = Vec::with_capacity(count);
+    for &code in codes {
+        let (a, b, c) = if code < 0xf0 {
+            let [a, b] = edge_fifo.get((code >> 4) as usize);
+            let fec = (code & 15) as usize;
+
+            let c = if fec < fec_max {
+                let c = if fec == 0 {
+                    next += 1;
+                    next - 1
+                } else {
+                    vertex_fifo.get(fec)
+                };
+                vertex_fifo.push(c, fec == 0);
+                c
+            } else {
+                last = match fec {
+                    13 => last.wrapping_sub(1),
+                    14 => last.wrapping_add(1),
+                    _ => decode_index(&mut reader, last)?,
+                };
+                vertex_fifo.push(last, true);
+                last
+            };
+            (a, b, c)
+        } else {
+            let (fea, codeaux) = if code < 0xfe {
+                (0, codeaux_table[(code & 15) as usize])
+            } else {
+                let codeaux = reader.read_u8()?;
+                // A full codeaux of zero resets the next vertex counter.
+                if codeaux == 0 {
+                    next = 0;
+                }
+                (if code == 0xfe { 0 } else { 15 }, codeaux)
+            };
+            let feb = (codeaux >> 4) as usize;
+            let fec = (codeaux & 15) as usize;
+
+            // The next vertex counter is incremented for all three vertices before decoding
+            // the free indices, which matches the encoder.
+            let mut resolve = |fe: usize| match fe {
+                0 => {
+                    next += 1;
+                    next - 1
+                }
+                15 => 0,
+                _ => vertex_fifo.get(fe - 1),
+            };
+            let mut a = resolve(fea);
+            let mut b = resolve(feb);
+            let mut c = resolve(fec);
+            for (fe, index) in [(fea, &mut a), (feb, &mut b), (fec, &mut c)] {
+                if fe == 15 {
+                    last = decode_index(&mut reader, last)?;
+                    *index = last;
+                }
+            }
+
+            for (index, push) in [
+                (a, true),
+                (b, feb == 0 || feb == 15),
+                (c, fec == 0 || fec == 15),
+            ] {
+                vertex_fifo.push(index, push);
+            }
+            edge_fifo.push([b, a], true);
+            (a, b, c)
+        };
+
+        edge_fifo.push([c, b], true);
+        edge_fifo.push([a, c], true);
+        indices.extend([a, b, c]);
+    }
+
+    if !reader.data.is_empty() {
+        return Err(anyhow!("Unexpected data after the triangles"));
+    }
+    Ok(indices)
+}
+
+/// Decodes an index sequence. Each index is stored as a delta from one of two baselines.
+fn decode_index_sequence(data: &[u8], count: usize) -> Result<Vec<u32>> {
+    if data.len() < count.saturating_add(1 + 4) {
+        return Err(truncated());
+    }
+    check_header(data, SEQUENCE_HEADER, 1)?;
+    let mut reader = Reader::new(&data[1..data.len() - 4]);
+
+    let mut last = [0u32; 2];
+    let mut indices = Vec::with_capacity(count);
+    for _ in 0..count {
+        let value = reader.read_varint()?;
+        let baseline = (value & 1) as usize;
+        let delta = unzigzag32(value >> 1);
+        last[baseline] = last[baseline].wrapping_add(delta);
+        indices.push(last[baseline]);
+    }
+
+    if !reader.data.is_empty() {
+        return Err(anyhow!("Unexpected data after the indices"));
+    }
+    Ok(indices)
+}
+
+fn decode_index(reader: &mut Reader, last: u32) -> Result<u32> {
+    Ok(last.wrapping_add(unzigzag32(reader.read_varint()?)))
+}
+
+fn write_indices(indices: &[u32], stride: usize) -> Result<Vec<u8>> {
+    match stride {
+        2 => Ok(indices
+            .iter()
+            .flat_map(|&index| (index as u16).to_le_bytes())
+            .collect()),
+        4 => Ok(indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect()),
+        _ => Err(anyhow!("Invalid index stride {}", stride)),
+    }
+}
+
+/// Encodes a vertex buffer with elements of `stride` bytes, the inverse of
+/// [`decode_vertex_buffer`].
+pub fn encode_vertex_buffer(data: &[u8], stride: usize) -> Result<Vec<u8>> {
+    if stride == 0 || stride > 256 || !stride.is_multiple_of(4) {
+        return Err(anyhow!("Invalid vertex stride {}", stride));
+    }
+    if !data.len().is_multiple_of(stride) {
+        return Err(anyhow!("The data is not a multiple of the vertex stride"));
+    }
+
+    let first_vertex = data.get(..stride).unwrap_or(&[0; 256][..stride]).to_vec();
+    let mut last_vertex = first_vertex.clone();
+
+    let mut output = vec![VERTEX_HEADER];
+    let mut deltas = [0; VERTEX_BLOCK_MAX_SIZE];
+    for block in data.chunks(vertex_block_size(stride) * stride) {
+        let block_count = block.len() / stride;
+        let aligned_count = block_count.next_multiple_of(BYTE_GROUP_SIZE);
+
+        for (k, last) in last_vertex.iter_mut().enumerate() {
+            deltas.fill(0);
+            for (i, delta) in deltas[..block_count].iter_mut().enumerate() {
+                let value = block[i * stride + k];
+                *delta = zigzag8(value.wrapping_sub(*last));
+                *last = value;
+            }
+            encode_bytes(&mut output, &deltas[..aligned_count]);
+        }
+    }
+
+    output.resize(output.len() + stride.max(TAIL_MAX_SIZE) - stride, 0);
+    output.extend_from_slice(&first_vertex);
+    Ok(output)
+}
+
+/// Packs each group of 16 values with the number of bits that takes the least space.
+fn encode_bytes(output: &mut Vec<u8>, values: &[u8]) {
+    let header_start = output.len();
+    let num_groups = values.len() / BYTE_GROUP_SIZE;
+    output.resize(header_start + num_groups.div_ceil(4), 0);
+
+    for (group, values) in values.chunks_exact(BYTE_GROUP_SIZE).enumerate() {
+        let mut best = (3, BYTE_GROUP_SIZE);
+        for (bits_log2, bits) in [(0, 0), (1, 2), (2, 4)] {
+            let size = if bits == 0 {
+                if values.iter().all(|&value| value == 0) {
+                    0
+                } else {
+                    usize::MAX
+                }
+            } else {
+                let sentinel = (1 << bits) - 1;
+                BYTE_GROUP_SIZE * bits / 8
+                    + values.iter().filter(|&&value| value >= sentinel).count()
+            };
+            if size < best.1 {
+                best = (bits_log2, size);
+            }
+        }
+
+        output[header_start + group / 4] |= best.0 << ((group % 4) * 2);
+        match best.0 {
+            0 => {}
+            1 => encode_bytes_group(output, values, 2),
+            2 => encode_bytes_group(output, values, 4),
+            _ => output.extend_from_slice(values),
+        }
+    }
+}
+
+fn encode_bytes_group(output: &mut Vec<u8>, values: &[u8], bits: usize) {
+    let sentinel = (1 << bits) - 1;
+    for packed in values.chunks_exact(8 / bits) {
+        output.push(
+            packed
+                .iter()
+                .fold(0, |byte, &value| (byte << bits) | value.min(sentinel)),
+        );
+    }
+    output.extend(values.iter().filter(|&&value| value >= sentinel));
+}
+
+/// Encodes a triangle list, the inverse of [`decode_index_buffer`]. The first version of the
+/// encoding is used, since every decoder supports it.
+pub fn encode_index_buffer(indices: &[u32]) -> Result<Vec<u8>> {
+    if !indices.len().is_multiple_of(3) {
+        return Err(anyhow!("The number of indices is not a multiple of 3"));
+    }
+
+    let mut codes = Vec::with_capacity(indices.len() / 3);
+    let mut data = Vec::new();
+    let mut edge_fifo = Fifo::new([u32::MAX; 2]);
+    let mut vertex_fifo = Fifo::new(u32::MAX);
+    let mut next = 0u32;
+    let mut last = 0u32;
+
+    for triangle in indices.chunks_exact(3) {
+        let rotate = |rotation: usize| [0, 1, 2].map(|i| triangle[(rotation + i) % 3]);
+
+        match find_triangle_edge(&edge_fifo, triangle) {
+            Some((fe, rotation)) if fe < 15 => {
+                let [a, b, c] = rotate(rotation);
+                let fec = match vertex_fifo.find(c) {
+                    Some(fc) if (1..15).contains(&fc) => fc,
+                    _ if c == next => {
+                        next += 1;
+                        0
+                    }
+                    _ => 15,
+                };
+                codes.push(((fe << 4) | fec) as u8);
+
+                if fec == 15 {
+                    encode_index(&mut data, c, last);
+                    last = c;
+                }
+                if fec == 0 || fec == 15 {
+                    vertex_fifo.push(c, true);
+                }
+                edge_fifo.push([c, b], true);
+                edge_fifo.push([a, c], true);
+            }
+            _ => {
+                // The triangle is rotated so that the next vertex, if any, comes first.
+                let rotation = triangle[1..]
+                    .iter()
+                    .position(|&index| index == next)
+                    .map_or(0, |position| position + 1);
+                let [a, b, c] = rotate(rotation);
+
+                let fb = vertex_fifo.find(b);
+                let fc = vertex_fifo.find(c);
+                let mut free_or_next = |index: u32| {
+                    if index == next {
+                        next += 1;
+                        0
+                    } else {
+                        15
+                    }
+                };
+                let fea = free_or_next(a);
+                let feb = fb
+                    .filter(|&fb| fb < 14)
+                    .map_or_else(|| free_or_next(b), |fb| fb + 1);
+                let fec = fc
+                    .filter(|&fc| fc < 14)
+                    .map_or_else(|| free_or_next(c), |fc| fc + 1);
+
+                let codeaux = ((feb << 4) | fec) as u8;
+                match CODEAUX_TABLE.iter().position(|&entry| entry == codeaux) {
+                    Some(index) if fea == 0 && index < 14 => codes.push(0xf0 | index as u8),
+                    _ => {
+                        codes.push(0xfe | (fea & 1) as u8);
+                        data.push(codeaux);
+                    }
+                }
+
+                for (fe, index) in [(fea, a), (feb, b), (fec, c)] {
+                    if fe == 15 {
+                        encode_index(&mut data, index, last);
+                        last = index;
+                    }
+                }
+                for (fe, index) in [(fea, a), (feb, b), (fec, c)] {
+                    if fe == 0 || fe == 15 {
+                        vertex_fifo.push(index, true);
+                    }
+                }
+                edge_fifo.push([b, a], true);
+                edge_fifo.push([c, b], true);
+                edge_fifo.push([a, c], true);
+            }
+        }
+    }
+
+    let mut output = vec![INDEX_HEADER];
+    output.append(&mut codes);
+    output.append(&mut data);
+    output.extend_from_slice(&CODEAUX_TABLE);
+    Ok(output)
+}
+
+/// Returns the position in the FIFO of an edge of the triangle and the rotation of the triangle
+/// that makes it the first edge.
+fn find_triangle_edge(edge_fifo: &Fifo<[u32; 2]>, triangle: &[u32]) -> Option<(usize, usize)> {
+    let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+    (0..16).find_map(|i| {
+        [[a, b], [b, c], [c, a]]
+            .iter()
+            .position(|&edge| edge == edge_fifo.get(i))
+            .map(|rotation| (i, rotation))
+    })
+}
+
+fn encode_index(data: &mut Vec<u8>, index: u32, last: u32) {
+    let delta = index.wrapping_sub(last);
+    let mut value = (delta << 1) ^ ((delta as i32 >> 31) as u32);
+    loop {
+        let byte = (value & 127) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte);
+            break;
+        }
+        data.push(byte | 128);
+    }
+}
+
+/// Reconstructs unit vectors stored as 8-bit or 16-bit octahedral coordinates. The fourth
+/// component is left untouched.
+fn decode_octahedral(data: &mut [u8], stride: usize) -> Result<()> {
+    fn decode(x: f32, y: f32, z: f32, max: f32) -> [f32; 3] {
+        let z = z - x.abs() - y.abs();
+        let t = z.min(0.);
+        let x = x + if x >= 0. { t } else { -t };
+        let y = y + if y >= 0. { t } else { -t };
+        let scale = max / (x * x + y * y + z * z).sqrt();
+        [x, y, z].map(|value| (value * scale).round())
+    }
+
+    match stride {
+        4 => {
+            for element in data.chunks_exact_mut(4) {
+                let [x, y, z] =
+                    [element[0], element[1], element[2]].map(|value| value as i8 as f32);
+                let decoded = decode(x, y, z, i8::MAX as f32);
+                for (byte, value) in element.iter_mut().zip(decoded) {
+                    *byte = value as i8 as u8;
+                }
+            }
+        }
+        8 => {
+            for element in data.chunks_exact_mut(8) {
+                let [x, y, z, _] = read_i16x4(element).map(f32::from);
+                let decoded = decode(x, y, z, i16::MAX as f32);
+                for (bytes, value) in element.chunks_exact_mut(2).zip(decoded) {
+                    bytes.copy_from_slice(&(value as i16).to_le_bytes());
+                }
+            }
+        }
+        _ => {
+            return Err(anyhow!(
+                "Invalid stride {} for the octahedral filter",
+                stride
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Reconstructs unit quaternions stored as their three smallest components. The fourth value
+/// holds the index of the largest component in the lowest two bits and the scale of the others
+/// in the remaining bits.
+fn decode_quaternion(data: &mut [u8], stride: usize) -> Result<()> {
+    if stride != 8 {
+        return Err(anyhow!(
+            "Invalid stride {} for the quaternion filter",
+            stride
+        ));
+    }
+
+    for element in data.chunks_exact_mut(8) {
+        let values = read_i16x4(element);
+        let scale = std::f32::consts::FRAC_1_SQRT_2 / (values[3] | 3) as f32;
+        let [x, y, z] = [values[0], values[1], values[2]].map(|value| value as f32 * scale);
+        let w = (1. - x * x - y * y - z * z).max(0.).sqrt();
+
+        let largest = (values[3] & 3) as usize;
+        let mut decoded = [0; 4];
+        for (offset, value) in [w, x, y, z].into_iter().enumerate() {
+            decoded[(largest + offset) & 3] = (value * i16::MAX as f32).round() as i16;
+        }
+        for (bytes, value) in element.chunks_exact_mut(2).zip(decoded) {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Reconstructs floats stored as a signed 8-bit exponent and a signed 24-bit mantissa.
+fn decode_exponential(data: &mut [u8], stride: usize) -> Result<()> {
+    if !stride.is_multiple_of(4) {
+        return Err(anyhow!(
+            "Invalid stride {} for the exponential filter",
+            stride
+        ));
+    }
+
+    for bytes in data.chunks_exact_mut(4) {
+        let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let mantissa = ((value << 8) as i32) >> 8;
+        let exponent = (value as i32) >> 24;
+        let decoded = f32::from_bits(((exponent + 127) as u32) << 23) * mantissa as f32;
+        bytes.copy_from_slice(&decoded.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn read_i16x4(bytes: &[u8]) -> [i16; 4] {
+    [0, 1, 2, 3].map(|i| i16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]))
+}
+
+/// Checks the header byte of the data and returns the encoding version.
+fn check_header(data: &[u8], header: u8, max_version: u8) -> Result<u8> {
+    let byte = *data.first().ok_or_else(truncated)?;
+    if byte & 0xf0 != header {
+        return Err(anyhow!("Invalid header"));
+    }
+    let version = byte & 0x0f;
+    if version > max_version {
+        return Err(anyhow!("Unsupported encoding version {}", version));
+    }
+    Ok(version)
+}
+
+/// Returns the number of vertices in each block of a vertex buffer.
+fn vertex_block_size(stride: usize) -> usize {
+    ((VERTEX_BLOCK_SIZE_BYTES / stride) & !(BYTE_GROUP_SIZE - 1)).min(VERTEX_BLOCK_MAX_SIZE)
+}
+
+fn zigzag8(value: u8) -> u8 {
+    (value << 1) ^ ((value as i8 >> 7) as u8)
+}
+
+fn unzigzag8(value: u8) -> u8 {
+    (value >> 1) ^ (value & 1).wrapping_neg()
+}
+
+fn unzigzag32(value: u32) -> u32 {
+    (value >> 1) ^ (value & 1).wrapping_neg()
+}
+
+fn truncated() -> anyhow::Error {
+    anyhow!("The compressed data is truncated")
+}
+
+/// A ring buffer of the 16 most recent edges or vertices.
+struct Fifo<T> {
+    items: [T; 16],
+    offset: usize,
+}
+
+impl<T: Copy> Fifo<T> {
+    fn new(item: T) -> Self {
+        Self {
+            items: [item; 16],
+            offset: 0,
+        }
+    }
+
+    /// Returns the item pushed `index` pushes ago, the most recent one being 0.
+    fn get(&self, index: usize) -> T {
+        self.items[self.offset.wrapping_sub(1 + index) & 15]
+    }
+
+    /// Returns how many pushes ago the item was pushed, if it's still in the FIFO.
+    fn find(&self, item: T) -> Option<usize>
+    where
+        T: PartialEq,
+    {
+        (0..16).find(|&index| self.get(index) == item)
+    }
+
+    /// Writes the item at the current position, which is only kept if `advance` is true.
+    fn push(&mut self, item: T, advance: bool) {
+        self.items[self.offset] = item;
+        self.offset = (self.offset + advance as usize) & 15;
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(truncated());
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads a 32-bit value stored in up to five groups of 7 bits.
+    fn read_varint(&mut self) -> Result<u32> {
+        let mut result = 0;
+        for group in 0..5 {
+            let byte = self.read_u8()?;
+            result |= ((byte & 127) as u32) << (group * 7);
+            if byte < 128 {
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const ENCODED_VERTICES: &[u8] = &[
+        160, 7, 0, 74, 74, 74, 74, 74, 74, 74, 74, 74, 74, 74, 74, 74, 74, 74, 255, 0, 0, 0, 74,
+        74, 74, 74, 1, 0, 2, 0, 8, 6, 5, 85, 85, 85, 85, 85, 85, 85, 255, 0, 0, 0, 5, 5, 5, 5, 0,
+        7, 0, 182, 34, 113, 250, 102, 45, 193, 170, 22, 125, 238, 90, 57, 205, 158, 255, 0, 0, 0,
+        10, 137, 226, 78, 6, 0, 36, 70, 138, 172, 239, 255, 255, 16, 16, 18, 18, 20, 255, 0, 0, 0,
+        24, 22, 26, 26, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 232, 3, 0, 0, 7, 0,
+    ];
+
+    const ENCODED_INDICES: &[u8] = &[
+        224, 240, 16, 0, 240, 114, 254, 22, 112, 0, 118, 135, 86, 103, 120, 169, 134, 101, 137,
+        104, 152, 1, 105, 0, 0,
+    ];
+
+    fn to_u16s(bytes: &[u8]) -> Vec<u16> {
+        bytes
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect()
+    }
+
+    fn to_bytes<T: Copy, const N: usize>(values: &[T], to_le_bytes: fn(T) -> [u8; N]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|&value| to_le_bytes(value))
+            .collect()
+    }
+
+    #[test]
+    fn vertex_buffer() {
+        let expected: Vec<u16> = (0..20u32)
+            .flat_map(|i| {
+                [i * 37, 1000 - i * 3, (i * i * 91) & 0xffff, 7].map(|value| value as u16)
+            })
+            .collect();
+
+        let decoded = decode(ENCODED_VERTICES, 20, 8, Mode::Attributes, Filter::None).unwrap();
+        assert_eq!(expected, to_u16s(&decoded));
+
+        assert!(decode(ENCODED_VERTICES, 16, 8, Mode::Attributes, Filter::None).is_err());
+        assert!(decode(
+            &ENCODED_VERTICES[..60],
+            20,
+            8,
+            Mode::Attributes,
+            Filter::None
+        )
+        .is_err());
+        assert!(decode(ENCODED_VERTICES, 20, 6, Mode::Attributes, Filter::None).is_err());
+        // Counts that the data can't hold are rejected before allocating the vertices.
+        for count in [1_000_000_000_000, usize::MAX] {
+            assert!(decode(ENCODED_VERTICES, count, 8, Mode::Attributes, Filter::None).is_err());
+            assert!(decode(ENCODED_INDICES, count, 4, Mode::Indices, Filter::None).is_err());
+        }
+    }
+
+    #[test]
+    fn index_buffer() {
+        // The encoder rotates some triangles, which preserves their winding.
+        let expected = vec![
+            0, 1, 2, 2, 1, 3, 2, 3, 4, 5, 6, 7, 0, 2, 5, 8, 1, 9, 9, 1, 3,
+        ];
+
+        let decoded = decode(ENCODED_INDICES, 21, 2, Mode::Triangles, Filter::None).unwrap();
+        assert_eq!(expected, to_u16s(&decoded));
+
+        let decoded = decode(ENCODED_INDICES, 21, 4, Mode::Triangles, Filter::None).unwrap();
+        assert_eq!(
+            to_bytes(&expected, |index: u16| (index as u32).to_le_bytes()),
+            decoded
+        );
+
+        assert!(decode(ENCODED_INDICES, 20, 2, Mode::Triangles, Filter::None).is_err());
+        assert!(decode(&ENCODED_INDICES[..20], 21, 2, Mode::Triangles, Filter::None).is_err());
+    }
+
+    #[test]
+    fn encode_buffers() {
+        let vertices = decode(ENCODED_VERTICES, 20, 8, Mode::Attributes, Filter::None).unwrap();
+        assert_eq!(
+            ENCODED_VERTICES,
+            encode_vertex_buffer(&vertices, 8).unwrap()
+        );
+
+        let indices = [
+            0, 1, 2, 2, 1, 3, 2, 3, 4, 5, 6, 7, 0, 2, 5, 9, 8, 1, 3, 9, 1,
+        ];
+        assert_eq!(ENCODED_INDICES, encode_index_buffer(&indices).unwrap());
+
+        // Large deltas need several bytes and several blocks.
+        let vertices: Vec<u8> = (0..1000u32)
+            .flat_map(|i| (i * i).wrapping_mul(7919).to_le_bytes())
+            .collect();
+        let encoded = encode_vertex_buffer(&vertices, 4).unwrap();
+        assert_eq!(
+            vertices,
+            decode(&encoded, 1000, 4, Mode::Attributes, Filter::None).unwrap()
+        );
+
+        let indices: Vec<u32> = (0..300)
+            .map(|i| (i * 7 + i / 3 * 100_000) % 70_001)
+            .collect();
+        let encoded = encode_index_buffer(&indices).unwrap();
+        let decoded = decode(&encoded, 300, 4, Mode::Triangles, Filter::None).unwrap();
+        assert_eq!(to_bytes(&indices, u32::to_le_bytes), decoded);
+    }
+
+    #[test]
+    fn index_buffer_v1() {
+        // Free indices next to the previous one are stored in the triangle code.
+        let mut data = vec![0xe1, 0xf0, 0x1f, 0x0e, 0x0d, 10];
+        data.extend([0; 16]);
+
+        let decoded = decode(&data, 12, 4, Mode::Triangles, Filter::None).unwrap();
+        let indices: Vec<u32> = decoded
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        assert_eq!(vec![0, 1, 2, 2, 1, 5, 2, 5, 6, 2, 6, 5], indices);
+    }
+
+    #[test]
+    fn index_sequence() {
+        let data = [0xd1, 0, 4, 0x29, 4, 0x0b, 0, 0, 0, 0];
+        let decoded = decode(&data, 5, 2, Mode::Indices, Filter::None).unwrap();
+        assert_eq!(vec![0, 1, 10, 2, 7], to_u16s(&decoded));
+
+        assert!(decode(&data, 6, 2, Mode::Indices, Filter::None).is_err());
+        assert!(decode(&[0xd2, 0, 0, 0, 0, 0], 1, 2, Mode::Indices, Filter::None).is_err());
+    }
+
+    #[test]
+    fn octahedral_filter() {
+        let mut data = vec![0, 0, 127, 5, 127, 127, 127, 0];
+        decode_octahedral(&mut data, 4).unwrap();
+        assert_eq!(vec![0, 0, 127, 5, 0, 0, -127i8 as u8, 0], data);
+
+        let mut data = to_bytes(&[32767i16, 32767, 32767, 0], i16::to_le_bytes);
+        decode_octahedral(&mut data, 8).unwrap();
+        assert_eq!(to_bytes(&[0i16, 0, -32767, 0], i16::to_le_bytes), data);
+    }
+
+    #[test]
+    fn quaternion_filter() {
+        // The largest component is w, and the scale is the largest possible one.
+        let mut data = to_bytes(&[0i16, 0, 32767, 32767, 0, 0, 0, 32767], i16::to_le_bytes);
+        decode_quaternion(&mut data, 8).unwrap();
+        assert_eq!(
+            to_bytes(&[0i16, 0, 23170, 23170, 0, 0, 0, 32767], i16::to_le_bytes),
+            data
+        );
+
+        // The largest component is x.
+        let mut data = to_bytes(&[0i16, 0, 0, 32764], i16::to_le_bytes);
+        decode_quaternion(&mut data, 8).unwrap();
+        assert_eq!(to_bytes(&[32767i16, 0, 0, 0], i16::to_le_bytes), data);
+    }
+
+    #[test]
+    fn exponential_filter() {
+        let mut data = to_bytes(
+            &[(-2i32 << 24) | 6, (3 << 24) | (-5i32 & 0xffffff)],
+            i32::to_le_bytes,
+        );
+        decode_exponential(&mut data, 8).unwrap();
+        assert_eq!(to_bytes(&[1.5f32, -40.], f32::to_le_bytes), data);
+    }
+}
@@ -1,11 +1,33 @@
-pub use {exporter::GltfExporter, importer::GltfImporter};
+pub use {
+    exporter::{GltfCompression, GltfExporter, GltfFormat, GltfInterpolation, KeyframeReduction},
+    importer::GltfImporter,
+    mapping::JointMapping,
+    uri::rename_buffer_file,
+};
 
 use glam::{Mat4, Vec4};
 
 use crate::scene::Scene;
 
+/// The name of the node that stores the root motion kept apart from the skeleton
+/// ([`Keyframe::root_motion`]).
+///
+/// [`Keyframe::root_motion`]: crate::scene::Keyframe::root_motion
+const ROOT_MOTION_NODE_NAME: &str = "root_motion";
+
+/// The name of the node whose X coordinate stores the FRM option of each frame
+/// ([`Keyframe::option`]).
+///
+/// [`Keyframe::option`]: crate::scene::Keyframe::option
+const FRAME_OPTIONS_NODE_NAME: &str = "frm_options";
+
+mod accessor;
 mod exporter;
 mod importer;
+mod keyframes;
+mod mapping;
+mod meshopt;
+mod uri;
 
 fn transform(scene: &Scene) -> Scene {
     let mut scene = scene.clone();
@@ -31,6 +53,7 @@ fn transform(scene: &Scene) -> Scene {
     for animation in &mut scene.animations {
         for frame in &mut animation.frames {
             frame.translation.z *= -1.;
+            frame.root_motion.z *= -1.;
             for transform in &mut frame.transforms {
                 *transform = matrix.mul_mat4(transform).mul_mat4(&matrix.inverse());
             }
@@ -0,0 +1,77 @@
+use anyhow::Result;
+use gltf::json;
+
+/// Percent-encodes a file name so that it can be used as a relative URI. Only the unreserved
+/// characters of RFC 3986 are kept as they are.
+pub fn encode(name: &str) -> String {
+    let mut uri = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Decodes the percent-encoded characters of a relative URI. Returns `None` if an escape
+/// sequence is invalid or the decoded bytes are not valid UTF-8.
+pub fn decode(uri: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut rest = uri.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Replaces the file `from` with the file `to` in the URIs of the buffers of a ".gltf" file,
+/// for when the files are renamed after exporting them.
+pub fn rename_buffer_file(gltf: &[u8], from: &str, to: &str) -> Result<Vec<u8>> {
+    let mut value: json::Value = json::deserialize::from_slice(gltf)?;
+    if let Some(buffers) = value["buffers"].as_array_mut() {
+        for buffer in buffers {
+            if buffer["uri"].as_str().and_then(decode).as_deref() == Some(from) {
+                buffer["uri"] = encode(to).into();
+            }
+        }
+    }
+    Ok(json::serialize::to_string_pretty(&value)?.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        assert_eq!("goblin_1.bin", encode("goblin_1.bin"));
+        assert_eq!("red%20goblin%20%231%25.bin", encode("red goblin #1%.bin"));
+        assert_eq!("%C3%A9p%C3%A9e.bin", encode("épée.bin"));
+
+        for name in ["red goblin #1%.bin", "épée.bin"] {
+            assert_eq!(Some(String::from(name)), decode(&encode(name)));
+        }
+        assert_eq!(None, decode("goblin%2.bin"));
+        assert_eq!(None, decode("goblin%zz.bin"));
+    }
+
+    #[test]
+    fn renamed_buffer_file() {
+        let gltf = br#"{"buffers": [{"byteLength": 4, "uri": "red%20goblin.bin"}]}"#;
+
+        let renamed = rename_buffer_file(gltf, "red goblin.bin", "red goblin_1.bin").unwrap();
+        let value: json::Value = json::deserialize::from_slice(&renamed).unwrap();
+        assert_eq!("red%20goblin_1.bin", value["buffers"][0]["uri"]);
+    }
+}
@@ -1,6 +1,9 @@
 pub use self::{
-    frm::{FrmExporter, FrmImporter},
-    gltf::{GltfExporter, GltfImporter},
+    frm::{FrmExporter, FrmImporter, FrmVersion, RootMotion},
+    gltf::{
+        GltfCompression, GltfExporter, GltfFormat, GltfImporter, GltfInterpolation, JointMapping,
+        KeyframeReduction,
+    },
     p3m::{P3mExporter, P3mImporter},
 };
 
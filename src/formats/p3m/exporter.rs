@@ -15,7 +15,7 @@ use super::internal::{
 pub struct P3mExporter {}
 
 impl Exporter for P3mExporter {
-    fn export(&self, scene: &Scene) -> Result<Vec<Asset>> {
+    fn export(&self, scene: &Scene) -> Result<(Vec<Asset>, Vec<String>)> {
         let mut result = Vec::new();
         for mesh in &scene.meshes {
             let (position_bones, angle_bones) = convert_joints(&scene.skeleton);
@@ -41,7 +41,7 @@ impl Exporter for P3mExporter {
 
             result.push(asset);
         }
-        Ok(result)
+        Ok((result, Vec::new()))
     }
 }
 
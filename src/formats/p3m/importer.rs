@@ -13,7 +13,7 @@ use super::internal::{AngleBone, P3m, PositionBone, SkinVertex, INVALID_BONE_IND
 pub struct P3mImporter {}
 
 impl Importer for P3mImporter {
-    fn import(&self, asset: &Asset, scene: &mut Scene) -> Result<()> {
+    fn import(&self, asset: &Asset, scene: &mut Scene) -> Result<Vec<String>> {
         let p3m = P3m::from_bytes(&asset.bytes)
             .context("Failed to deserialize the bytes of the P3M asset")?;
 
@@ -22,7 +22,7 @@ impl Importer for P3mImporter {
             .meshes
             .push(convert_mesh(&p3m, asset.name().to_string(), scene));
 
-        Ok(())
+        Ok(Vec::new())
     }
 
     fn extensions(&self) -> &[&str] {
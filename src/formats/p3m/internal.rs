@@ -379,7 +379,7 @@ impl Default for MeshVertex {
 }
 
 mod util {
-    use std::io::{Cursor, Error, ErrorKind, Read, Result, Write};
+    use std::io::{Cursor, Error, Read, Result, Write};
 
     use byteorder::WriteBytesExt;
 
@@ -395,7 +395,7 @@ mod util {
 
         match String::from_utf8(bytes) {
             Ok(string) => Ok(string),
-            Err(error) => Err(Error::new(ErrorKind::Other, error.to_string())),
+            Err(error) => Err(Error::other(error.to_string())),
         }
     }
 
@@ -403,7 +403,7 @@ mod util {
     /// length allowed, the remaining bytes are filled with zero. If it's longer, it's truncated.
     pub fn write_string(bytes: &mut Vec<u8>, string: &str, max_len: usize) -> Result<()> {
         let len = usize::min(string.len(), max_len);
-        bytes.write_all(string[0..len].as_bytes())?;
+        bytes.write_all(&string.as_bytes()[0..len])?;
 
         // Set the remaining bytes to zero, if any.
         for _ in 0..(max_len - len) {
@@ -501,70 +501,72 @@ mod tests {
     }
 
     fn data() -> (P3m, &'static [u8]) {
-        let mut p3m = P3m::default();
-        p3m.position_bones = vec![
-            PositionBone {
-                position: [0., 0., 0.],
-                children: vec![0],
-            },
-            PositionBone {
-                position: [1., 0., 0.],
-                children: vec![1],
-            },
-        ];
-        p3m.angle_bones = vec![
-            AngleBone {
-                position: [0., 0., 0.],
-                scale: 0.,
-                children: vec![1],
-            },
-            AngleBone {
-                position: [0., 0., 0.],
-                scale: 0.,
-                children: Vec::new(),
-            },
-        ];
-        p3m.faces = vec![[0, 1, 2]];
-        p3m.skin_vertices = vec![
-            SkinVertex {
-                position: [1., 0., 0.],
-                weight: 1.,
-                bone_index: 0,
-                uv: [0., 0.],
-                normal: [1., 0., 0.],
-            },
-            SkinVertex {
-                position: [0., 1., 0.],
-                weight: 1.,
-                bone_index: 0,
-                uv: [0.5, 0.5],
-                normal: [1., 0., 0.],
-            },
-            SkinVertex {
-                position: [1., 0., 1.],
-                weight: 1.,
-                bone_index: 1,
-                uv: [1., 1.],
-                normal: [1., 0., 0.],
-            },
-        ];
-        p3m.mesh_vertices = vec![
-            MeshVertex {
-                position: [1., 0., 0.],
-                uv: [0., 0.],
-                normal: [1., 0., 0.],
-            },
-            MeshVertex {
-                position: [0., 1., 0.],
-                uv: [0.5, 0.5],
-                normal: [1., 0., 0.],
-            },
-            MeshVertex {
-                position: [0., 0., 1.],
-                uv: [1., 1.],
-                normal: [1., 0., 0.],
-            },
-        ];
+        let p3m = P3m {
+            position_bones: vec![
+                PositionBone {
+                    position: [0., 0., 0.],
+                    children: vec![0],
+                },
+                PositionBone {
+                    position: [1., 0., 0.],
+                    children: vec![1],
+                },
+            ],
+            angle_bones: vec![
+                AngleBone {
+                    position: [0., 0., 0.],
+                    scale: 0.,
+                    children: vec![1],
+                },
+                AngleBone {
+                    position: [0., 0., 0.],
+                    scale: 0.,
+                    children: Vec::new(),
+                },
+            ],
+            faces: vec![[0, 1, 2]],
+            skin_vertices: vec![
+                SkinVertex {
+                    position: [1., 0., 0.],
+                    weight: 1.,
+                    bone_index: 0,
+                    uv: [0., 0.],
+                    normal: [1., 0., 0.],
+                },
+                SkinVertex {
+                    position: [0., 1., 0.],
+                    weight: 1.,
+                    bone_index: 0,
+                    uv: [0.5, 0.5],
+                    normal: [1., 0., 0.],
+                },
+                SkinVertex {
+                    position: [1., 0., 1.],
+                    weight: 1.,
+                    bone_index: 1,
+                    uv: [1., 1.],
+                    normal: [1., 0., 0.],
+                },
+            ],
+            mesh_vertices: vec![
+                MeshVertex {
+                    position: [1., 0., 0.],
+                    uv: [0., 0.],
+                    normal: [1., 0., 0.],
+                },
+                MeshVertex {
+                    position: [0., 1., 0.],
+                    uv: [0.5, 0.5],
+                    normal: [1., 0., 0.],
+                },
+                MeshVertex {
+                    position: [0., 0., 1.],
+                    uv: [1., 1.],
+                    normal: [1., 0., 0.],
+                },
+            ],
+            ..Default::default()
+        };
 
         const DATA: [u8; 619] = [
             0x50, 0x65, 0x72, 0x66, 0x61, 0x63, 0x74, 0x20, 0x33, 0x44, 0x20, 0x4d, 0x6f, 0x64,
@@ -1,4 +1,8 @@
 pub mod asset;
 pub mod conversion;
 pub mod formats;
+pub mod mirror;
+pub mod profile;
+pub mod retarget;
 pub mod scene;
+pub mod skinning;
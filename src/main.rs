@@ -1,16 +1,230 @@
-use std::io;
 use std::io::prelude::*;
+use std::{io, path::Path, process, str::FromStr};
 
-use chaseconv::conversion;
+use anyhow::{bail, Context, Result};
+use chaseconv::{
+    conversion::{self, NameCollision},
+    formats::RootMotion,
+    profile::SkeletonProfile,
+    retarget::JointMatching,
+    scene::{Animation, Scene},
+};
+
+const USAGE: &str = "\
+Usage:
+  chaseconv [FILES]...
+  chaseconv trim <INPUT> <OUTPUT> <FIRST FRAME> <LAST FRAME>
+  chaseconv concat <INPUT> <OTHER INPUT> <OUTPUT> [BLEND FRAMES]
+  chaseconv speed <INPUT> <OUTPUT> <SPEED>
+  chaseconv reverse <INPUT> <OUTPUT>
+  chaseconv loop <INPUT> <OUTPUT> [BLEND FRAMES]
+  chaseconv mirror <INPUT> <OUTPUT> [PROFILE]
+  chaseconv retarget <INPUT> <SOURCE MODEL> <TARGET MODEL> <OUTPUT> [index|names|hierarchy]
+
+Without a command, the files are converted interactively. The commands edit every animation
+of the input and write the result to the output, which can be a FRM or a GLB file. Without a
+profile, \"mirror\" uses the matching profile of the \"profiles\" directory. FRM files are only
+matched by their number of joints, so the profile must be passed if several have the same.
+
+\"retarget\" moves the skeleton of the target model (P3M or GLB) with the animations made for
+the source model, matching their joints by hierarchy unless specified. Only the rotations of
+the joints and the root translation are transferred; joint translations and scales are lost.";
+
+/// The number of blend frames used when the commands don't specify it.
+const DEFAULT_BLEND_FRAMES: usize = 5;
 
-// TODO: add CLI.
 fn main() {
-    let files: Vec<_> = std::env::args().skip(1).collect();
+    let args: Vec<_> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some(
+            command @ ("trim" | "concat" | "speed" | "reverse" | "loop" | "mirror" | "retarget"),
+        ) => {
+            if let Err(err) = edit(command, &args[1..]) {
+                eprintln!("Error: {:#}\n\n{}", err, USAGE);
+                process::exit(1);
+            }
+        }
+        Some("help" | "-h" | "--help") => eprintln!("{}", USAGE),
+        _ => convert(&args),
+    }
+}
+
+/// Edits the animations of an input file with a command and exports them to an output file.
+fn edit(command: &str, args: &[String]) -> Result<()> {
+    let arg = |index: usize| {
+        args.get(index)
+            .map(String::as_str)
+            .with_context(|| format!("Missing arguments for \"{}\"", command))
+    };
+    let blend_frames = |index: usize| {
+        args.get(index)
+            .map_or(Ok(DEFAULT_BLEND_FRAMES), |arg| parse(arg, "blend frames"))
+    };
+    // The root is moved like in the game, which is also how it's exported to FRM files.
+    let root_motion = RootMotion::default();
+    let profiles = conversion::profiles();
 
+    let input = arg(0)?;
+    let mut scene = conversion::import_file(input, root_motion, &profiles)?;
+    if scene.animations.is_empty() {
+        bail!("\"{}\" has no animations", input);
+    }
+
+    let output = match command {
+        "trim" => {
+            let first: usize = parse(arg(2)?, "first frame")?;
+            let last: usize = parse(arg(3)?, "last frame")?;
+            edit_animations(&mut scene, |animation| animation.trim(first..=last));
+            arg(1)?
+        }
+        "concat" => {
+            let other_input = arg(1)?;
+            let other = conversion::import_file(other_input, root_motion, &profiles)?;
+            let [other] = other.animations.as_slice() else {
+                bail!("\"{}\" must have a single animation", other_input);
+            };
+            let blend_frames = blend_frames(3)?;
+            edit_animations(&mut scene, |animation| {
+                animation.concatenate(other, blend_frames)
+            });
+            arg(2)?
+        }
+        "speed" => {
+            let speed: f32 = parse(arg(2)?, "speed")?;
+            if !(speed > 0. && speed.is_finite()) {
+                bail!("The speed must be a positive number");
+            }
+            edit_animations(&mut scene, |animation| animation.change_speed(speed));
+            arg(1)?
+        }
+        "reverse" => {
+            edit_animations(&mut scene, Animation::reverse);
+            arg(1)?
+        }
+        "loop" => {
+            let blend_frames = blend_frames(2)?;
+            edit_animations(&mut scene, |animation| animation.close_loop(blend_frames));
+            arg(1)?
+        }
+        "mirror" => {
+            let profile = match args.get(2) {
+                Some(path) => SkeletonProfile::from_file(Path::new(path))
+                    .with_context(|| format!("Failed to read the skeleton profile \"{}\"", path))?,
+                None => find_profile(&scene, &profiles)
+                    .cloned()
+                    .with_context(|| format!("Can't pick a skeleton profile for \"{}\"", input))?,
+            };
+            edit_animations(&mut scene, |animation| animation.mirror(&profile));
+            arg(1)?
+        }
+        "retarget" => {
+            let source = import_model(arg(1)?, &profiles)?;
+            let mut target = import_model(arg(2)?, &profiles)?;
+            let matching = match args.get(4).map_or("hierarchy", String::as_str) {
+                "index" => JointMatching::Index,
+                "hierarchy" => JointMatching::Hierarchy,
+                "names" => {
+                    let find = |scene: &Scene, path: &str| {
+                        SkeletonProfile::find(&profiles, &scene.skeleton)
+                            .with_context(|| format!("No skeleton profile matches \"{}\"", path))
+                    };
+                    JointMatching::Names {
+                        source: find(&source, arg(1)?)?,
+                        target: find(&target, arg(2)?)?,
+                    }
+                }
+                other => bail!("Invalid joint matching: \"{}\"", other),
+            };
+            target.animations = scene
+                .animations
+                .iter()
+                .map(|animation| animation.retarget(&source.skeleton, &target.skeleton, matching))
+                .collect();
+            scene = target;
+            arg(3)?
+        }
+        _ => unreachable!(),
+    };
+
+    conversion::export_file(&scene, output, root_motion, &profiles)?;
+    eprintln!("Exported \"{}\" successfully!", output);
+    Ok(())
+}
+
+/// Returns the profile that matches the skeleton of the scene. FRM animations have no skeleton,
+/// so only their number of joints can be compared, and several characters may share it. Then,
+/// a profile is only picked if it's the single one with that number of joints.
+fn find_profile<'a>(scene: &Scene, profiles: &'a [SkeletonProfile]) -> Result<&'a SkeletonProfile> {
+    if !scene.skeleton.is_empty() {
+        return SkeletonProfile::find(profiles, &scene.skeleton)
+            .context("No skeleton profile matches its skeleton. Pass the path of a profile");
+    }
+
+    let num_joints = scene
+        .animations
+        .first()
+        .and_then(|animation| animation.frames.first())
+        .map_or(0, |frame| frame.transforms.len());
+    let candidates: Vec<_> = profiles
+        .iter()
+        .filter(|profile| profile.joint_names.len() == num_joints)
+        .collect();
+    match candidates.as_slice() {
+        [profile] => Ok(profile),
+        [] => bail!(
+            "No skeleton profile has {} joints. Pass the path of a profile",
+            num_joints
+        ),
+        _ => {
+            let names: Vec<_> = candidates
+                .iter()
+                .map(|profile| profile.name.as_str())
+                .collect();
+            bail!(
+                "Several skeleton profiles have {} joints ({}). Pass the path of the right one",
+                num_joints,
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Imports a model whose skeleton is used for retargeting.
+fn import_model(path: &str, profiles: &[SkeletonProfile]) -> Result<Scene> {
+    let scene = conversion::import_file(path, RootMotion::default(), profiles)?;
+    if scene.skeleton.is_empty() {
+        bail!("\"{}\" has no skeleton", path);
+    }
+    Ok(scene)
+}
+
+fn edit_animations(scene: &mut Scene, edit: impl Fn(&Animation) -> Animation) {
+    scene.animations = scene.animations.iter().map(edit).collect();
+}
+
+fn parse<T: FromStr>(arg: &str, name: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    arg.parse()
+        .with_context(|| format!("Invalid {}: \"{}\"", name, arg))
+}
+
+/// Converts the files to a format selected interactively.
+fn convert(files: &[String]) {
     if !files.is_empty() {
         eprintln!("Trying to convert {} file(s)...\n", files.len());
 
-        let converters = conversion::converters();
+        let root_motion = if files
+            .iter()
+            .any(|file| file.to_lowercase().ends_with(".frm"))
+        {
+            select_root_motion()
+        } else {
+            RootMotion::default()
+        };
+        let mut converters = conversion::converters(root_motion, &conversion::profiles());
 
         let items: Vec<_> = converters.iter().map(|converter| converter.name).collect();
         let option = dialoguer::Select::new()
@@ -19,7 +233,8 @@ fn main() {
             .items(&items)
             .interact()
             .expect("Failed to select converter option");
-        let converter = &converters[option];
+        let converter = &mut converters[option];
+        converter.name_collision = select_name_collision();
 
         let out_path = dialoguer::Input::new()
             .with_prompt("Select the output directory")
@@ -29,7 +244,7 @@ fn main() {
             .expect("Failed to define output path");
 
         eprintln!();
-        converter.convert(&files, &out_path);
+        converter.convert(files, &out_path);
     } else {
         eprintln!("There were no input files. No files were converted.")
     }
@@ -37,6 +252,50 @@ fn main() {
     pause();
 }
 
+fn select_root_motion() -> RootMotion {
+    let modes = [
+        ("Move the root node (as in the game)", RootMotion::Root),
+        (
+            "Keep in place (remove horizontal motion)",
+            RootMotion::InPlace,
+        ),
+        (
+            "Move to a separate \"root_motion\" node",
+            RootMotion::Separate,
+        ),
+        ("Keep as stored in the FRM files", RootMotion::AsIs),
+    ];
+
+    let items: Vec<_> = modes.iter().map(|(name, _)| name).collect();
+    let option = dialoguer::Select::new()
+        .with_prompt("Select how the root motion of the animations is converted")
+        .default(0)
+        .items(&items)
+        .interact()
+        .expect("Failed to select root motion option");
+    modes[option].1
+}
+
+fn select_name_collision() -> NameCollision {
+    let modes = [
+        (
+            "Add a numeric suffix (e.g., \"mesh_1.p3m\")",
+            NameCollision::Suffix,
+        ),
+        ("Overwrite the previous file", NameCollision::Overwrite),
+        ("Stop the conversion", NameCollision::Fail),
+    ];
+
+    let items: Vec<_> = modes.iter().map(|(name, _)| name).collect();
+    let option = dialoguer::Select::new()
+        .with_prompt("Select what happens when several output files have the same name")
+        .default(0)
+        .items(&items)
+        .interact()
+        .expect("Failed to select name collision option");
+    modes[option].1
+}
+
 fn pause() {
     let mut stdin = io::stdin();
     let mut stdout = io::stdout();
@@ -0,0 +1,216 @@
+use glam::{Mat3, Mat4, Vec3, Vec3A};
+
+use crate::{
+    profile::SkeletonProfile,
+    scene::{Animation, Keyframe},
+};
+
+/// The normal of the sagittal plane of the characters, which divides their left and right
+/// sides. Characters face the X axis, along which they move, so their sides lie along Z.
+pub const SAGITTAL_NORMAL: Vec3 = Vec3::Z;
+
+/// The characters that separate the words of joint names.
+const SEPARATORS: [char; 5] = ['_', '.', ' ', '-', ':'];
+
+/// The words that name the left and right sides in joint names.
+const SIDES: [(&str, &str); 5] = [
+    ("L", "R"),
+    ("l", "r"),
+    ("Left", "Right"),
+    ("left", "right"),
+    ("LEFT", "RIGHT"),
+];
+
+/// Returns the joint on the other side of each joint of the profile, or the joint itself if it
+/// has no counterpart (e.g., the spine). Joints are paired by their names: a joint named
+/// "L_Hand", "Hand.L" or "LeftHand" is paired with the joint named "R_Hand", "Hand.R" or
+/// "RightHand", respectively.
+pub fn mirror_joints(profile: &SkeletonProfile) -> Vec<usize> {
+    (0..profile.joint_names.len())
+        .map(|index| {
+            counterpart_name(&profile.joint_names[index])
+                .and_then(|name| profile.joint_index(&name))
+                .unwrap_or(index)
+        })
+        .collect()
+}
+
+/// Returns a copy of the animation reflected across the plane with the given normal, which
+/// passes through the origin. `joint_map` gives the joint on the other side of each joint, as
+/// returned by [`mirror_joints`], and each joint takes the reflected transform of its
+/// counterpart. The translations of the root are reflected too.
+///
+/// The joints are assumed to be aligned with the world axes in the bind pose, as in the
+/// skeletons of the game, so that the reflected transforms keep the joints on each side
+/// symmetrical.
+pub fn mirror(animation: &Animation, joint_map: &[usize], normal: Vec3) -> Animation {
+    let normal = normal.normalize();
+    let reflection = Mat4::from_mat3(Mat3::from_cols(
+        Vec3::X - 2. * normal.x * normal,
+        Vec3::Y - 2. * normal.y * normal,
+        Vec3::Z - 2. * normal.z * normal,
+    ));
+    let reflect = |vector: Vec3A| vector - 2. * vector.dot(normal.into()) * Vec3A::from(normal);
+
+    let frames = animation
+        .frames
+        .iter()
+        .map(|frame| Keyframe {
+            translation: reflect(frame.translation),
+            root_motion: reflect(frame.root_motion),
+            option: frame.option,
+            transforms: (0..frame.transforms.len())
+                .map(|index| {
+                    let counterpart = joint_map
+                        .get(index)
+                        .copied()
+                        .filter(|&counterpart| counterpart < frame.transforms.len())
+                        .unwrap_or(index);
+                    // Reflecting the transform on both sides keeps it a rotation.
+                    reflection * frame.transforms[counterpart] * reflection
+                })
+                .collect(),
+        })
+        .collect();
+
+    Animation {
+        name: animation.name.clone(),
+        sampling_rate: animation.sampling_rate,
+        frames,
+    }
+}
+
+/// Returns the name of the joint on the other side, or `None` if the name doesn't mention a
+/// side. Only the first word that mentions a side is swapped.
+fn counterpart_name(name: &str) -> Option<String> {
+    let mut result = String::with_capacity(name.len() + 1);
+    let mut swapped = false;
+    for piece in name.split_inclusive(&SEPARATORS[..]) {
+        let word = piece.trim_end_matches(&SEPARATORS[..]);
+        let counterpart = (!swapped).then(|| swap_side(word)).flatten();
+        swapped |= counterpart.is_some();
+
+        result.push_str(counterpart.as_deref().unwrap_or(word));
+        result.push_str(&piece[word.len()..]);
+    }
+    swapped.then_some(result)
+}
+
+/// Swaps the side mentioned by a word of a joint name. Single letters must be the whole word,
+/// whereas full side names can also start or end it at a change of case (e.g., "LeftHand" or
+/// "HandLeft", but not "Upright").
+fn swap_side(word: &str) -> Option<String> {
+    SIDES
+        .iter()
+        .flat_map(|&(left, right)| [(left, right), (right, left)])
+        .find_map(|(side, other)| {
+            if word == side {
+                Some(other.to_string())
+            } else if side.len() > 1 {
+                word.strip_prefix(side)
+                    .filter(|_| is_case_boundary(word, side.len()))
+                    .map(|rest| format!("{}{}", other, rest))
+                    .or_else(|| {
+                        word.strip_suffix(side)
+                            .filter(|rest| is_case_boundary(word, rest.len()))
+                            .map(|rest| format!("{}{}", rest, other))
+                    })
+            } else {
+                None
+            }
+        })
+}
+
+/// Returns whether a new word starts at the given byte index of a camel case name: an uppercase
+/// letter after a lowercase letter or a digit ("HandLeft"), the last uppercase letter of an
+/// acronym ("LEFTHand"), or a digit after a letter ("Left2").
+fn is_case_boundary(name: &str, index: usize) -> bool {
+    let mut before = name[..index].chars().rev();
+    let mut after = name[index..].chars();
+    match (before.next(), after.next(), after.next()) {
+        (Some(previous), Some(current), next) => {
+            ((previous.is_lowercase() || previous.is_ascii_digit()) && current.is_uppercase())
+                || (previous.is_uppercase()
+                    && current.is_uppercase()
+                    && next.is_some_and(char::is_lowercase))
+                || (previous.is_alphabetic() && current.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn counterpart_names() {
+        for (name, expected) in [
+            ("L_UpperArm", Some("R_UpperArm")),
+            ("Hand.R", Some("Hand.L")),
+            ("mixamorig:LeftHand", Some("mixamorig:RightHand")),
+            ("thigh_right", Some("thigh_left")),
+            ("FootLEFT", Some("FootRIGHT")),
+            ("Leg l", Some("Leg r")),
+            ("leftFoot", Some("rightFoot")),
+            ("LEFTHand", Some("RIGHTHand")),
+            ("Spine", None),
+            ("Lower_Back", None),
+            ("Upright", None),
+            ("Copyright_Bone", None),
+            ("Leftover", None),
+            ("handleft", None),
+            ("RIGHTEOUS", None),
+        ] {
+            assert_eq!(
+                expected.map(String::from),
+                counterpart_name(name),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn joint_pairs() {
+        let text = "bone_0 = Pelvis\nbone_1 = L_Thigh\nbone_2 = R_Thigh\nbone_3 = L_Tail";
+        let profile = SkeletonProfile::parse("test", text).unwrap();
+
+        assert_eq!(vec![0, 2, 1, 3], mirror_joints(&profile));
+    }
+
+    #[test]
+    fn mirrored_animation() {
+        let animation = Animation {
+            name: String::from("slash"),
+            sampling_rate: 55.,
+            frames: vec![Keyframe {
+                translation: Vec3A::new(1., 2., 3.),
+                root_motion: Vec3A::new(0., 0., -1.),
+                option: 2,
+                transforms: vec![
+                    Mat4::from_rotation_y(0.2),
+                    Mat4::from_rotation_x(0.5),
+                    Mat4::from_rotation_z(0.3),
+                ],
+            }],
+        };
+        let joint_map = [0, 2, 1];
+
+        let actual = mirror(&animation, &joint_map, SAGITTAL_NORMAL);
+        let frame = &actual.frames[0];
+        assert_eq!(Vec3A::new(1., 2., -3.), frame.translation);
+        assert_eq!(Vec3A::new(0., 0., 1.), frame.root_motion);
+        assert_eq!(2, frame.option);
+        // Rotations around the normal are kept, whereas rotations around the axes of the plane
+        // are inverted.
+        assert!(frame.transforms[0].abs_diff_eq(Mat4::from_rotation_y(-0.2), 1e-6));
+        assert!(frame.transforms[1].abs_diff_eq(Mat4::from_rotation_z(0.3), 1e-6));
+        assert!(frame.transforms[2].abs_diff_eq(Mat4::from_rotation_x(-0.5), 1e-6));
+
+        let actual = mirror(&actual, &joint_map, SAGITTAL_NORMAL);
+        assert_eq!(animation, actual);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{formats::p3m::internal::MAX_NUM_BONES, scene::Joint};
+
+/// The file extension of skeleton profile files.
+pub const PROFILE_EXTENSION: &str = "profile";
+
+/// Gives human-readable names to the joints of a character skeleton.
+///
+/// Profiles are stored in text files with one `bone_N = Name` pair per line. Optionally, the
+/// parent of a joint can be given after a colon (e.g., `bone_3 = L_UpperArm : bone_2`). If any
+/// parent is given, joints without one are considered roots and the hierarchy is used to pick
+/// the profile of a skeleton automatically. Lines starting with `#` are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkeletonProfile {
+    /// The name of the profile, usually the name of the character.
+    pub name: String,
+    /// The name of each joint, indexed by joint index.
+    pub joint_names: Vec<String>,
+    /// The parent of each joint, if the profile defines the hierarchy of the skeleton.
+    pub parents: Option<Vec<Option<usize>>>,
+}
+
+impl SkeletonProfile {
+    /// Reads a profile from a text file. The name of the profile is the name of the file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read skeleton profile \"{}\"", path.display()))?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        Self::parse(&name, &text)
+            .with_context(|| format!("Invalid skeleton profile \"{}\"", path.display()))
+    }
+
+    /// Reads all the profiles of a directory, sorted by name. Each profile is read separately,
+    /// so that the ones that are invalid can be skipped.
+    pub fn load_dir(path: &Path) -> Result<Vec<Result<Self>>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().unwrap_or_default() == PROFILE_EXTENSION {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        Ok(paths.iter().map(|path| Self::from_file(path)).collect())
+    }
+
+    pub fn parse(name: &str, text: &str) -> Result<Self> {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let mut joints: Vec<Option<(String, Option<usize>)>> = Vec::new();
+        let mut has_hierarchy = false;
+        for (number, line) in lines {
+            let (joint, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Line {}: expected \"bone_N = Name\"", number))?;
+            let index = parse_joint(joint)
+                .ok_or_else(|| anyhow!("Line {}: \"{}\" is not a valid joint", number, joint))?;
+
+            let (joint_name, parent) = match value.split_once(':') {
+                Some((joint_name, parent)) => {
+                    let parent = parse_joint(parent).ok_or_else(|| {
+                        anyhow!("Line {}: \"{}\" is not a valid joint", number, parent)
+                    })?;
+                    has_hierarchy = true;
+                    (joint_name.trim(), Some(parent))
+                }
+                None => (value.trim(), None),
+            };
+            if joint_name.is_empty() {
+                return Err(anyhow!("Line {}: the joint name is empty", number));
+            }
+
+            if index >= MAX_NUM_BONES {
+                return Err(anyhow!(
+                    "Line {}: \"bone_{}\" exceeds the maximum number of joints ({})",
+                    number,
+                    index,
+                    MAX_NUM_BONES
+                ));
+            }
+            if index >= joints.len() {
+                joints.resize(index + 1, None);
+            }
+            if joints[index].is_some() {
+                return Err(anyhow!(
+                    "Line {}: \"bone_{}\" is named twice",
+                    number,
+                    index
+                ));
+            }
+            joints[index] = Some((joint_name.to_string(), parent));
+        }
+
+        let joints = joints
+            .into_iter()
+            .enumerate()
+            .map(|(index, joint)| joint.ok_or_else(|| anyhow!("\"bone_{}\" has no name", index)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let joint_names: Vec<_> = joints.iter().map(|(name, _)| name.clone()).collect();
+        for (index, name) in joint_names.iter().enumerate() {
+            if joint_names[..index].contains(name) {
+                return Err(anyhow!("The name \"{}\" is used more than once", name));
+            }
+        }
+
+        let parents = if has_hierarchy {
+            let parents: Vec<_> = joints.iter().map(|&(_, parent)| parent).collect();
+            if let Some(&parent) = parents.iter().flatten().find(|&&p| p >= joints.len()) {
+                return Err(anyhow!("\"bone_{}\" is not defined", parent));
+            }
+            Some(parents)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            joint_names,
+            parents,
+        })
+    }
+
+    /// Returns the name of the joint with the given index.
+    pub fn joint_name(&self, index: usize) -> Option<&str> {
+        self.joint_names.get(index).map(String::as_str)
+    }
+
+    /// Returns the index of the joint with the given name.
+    pub fn joint_index(&self, name: &str) -> Option<usize> {
+        self.joint_names.iter().position(|joint| joint == name)
+    }
+
+    /// Returns whether the profile describes the given skeleton. Both must have the same number
+    /// of joints and, if the profile defines a hierarchy, the same parents.
+    pub fn matches(&self, skeleton: &[Joint]) -> bool {
+        if self.joint_names.len() != skeleton.len() {
+            return false;
+        }
+
+        match &self.parents {
+            Some(parents) => parents
+                .iter()
+                .zip(skeleton)
+                .all(|(&parent, joint)| parent == joint.parent),
+            None => true,
+        }
+    }
+
+    /// Returns the first profile that matches the given skeleton. Profiles that define a
+    /// hierarchy are preferred.
+    pub fn find<'a>(profiles: &'a [Self], skeleton: &[Joint]) -> Option<&'a Self> {
+        profiles
+            .iter()
+            .filter(|profile| profile.parents.is_some())
+            .chain(profiles.iter().filter(|profile| profile.parents.is_none()))
+            .find(|profile| profile.matches(skeleton))
+    }
+}
+
+/// Parses a joint written as "bone_N".
+pub(crate) fn parse_joint(text: &str) -> Option<usize> {
+    text.trim().strip_prefix("bone_")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let text = "# Elesis\nbone_1 = Spine : bone_0\nbone_0 = Pelvis\n";
+        let expected = SkeletonProfile {
+            name: String::from("elesis"),
+            joint_names: vec![String::from("Pelvis"), String::from("Spine")],
+            parents: Some(vec![None, Some(0)]),
+        };
+
+        let actual = SkeletonProfile::parse("elesis", text).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(Some("Spine"), actual.joint_name(1));
+        assert_eq!(Some(0), actual.joint_index("Pelvis"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(SkeletonProfile::parse("", "bone_1 = Spine").is_err());
+        assert!(SkeletonProfile::parse("", "bone_0 = A\nbone_1 = A").is_err());
+        assert!(SkeletonProfile::parse("", "bone_0 = A : bone_5").is_err());
+        assert!(SkeletonProfile::parse("", "spine = A").is_err());
+        let error = SkeletonProfile::parse("", "bone_99999999999 = A").unwrap_err();
+        assert!(error.to_string().contains("maximum number of joints"));
+    }
+
+    #[test]
+    fn find() {
+        let skeleton = vec![
+            Joint {
+                children: vec![1],
+                ..Default::default()
+            },
+            Joint {
+                parent: Some(0),
+                ..Default::default()
+            },
+        ];
+        let profiles = vec![
+            SkeletonProfile::parse("flat", "bone_0 = A\nbone_1 = B").unwrap(),
+            SkeletonProfile::parse("wrong", "bone_0 = A : bone_1\nbone_1 = B").unwrap(),
+            SkeletonProfile::parse("right", "bone_0 = A\nbone_1 = B : bone_0").unwrap(),
+        ];
+
+        let actual = SkeletonProfile::find(&profiles, &skeleton).unwrap();
+        assert_eq!("right", actual.name);
+        assert!(SkeletonProfile::find(&profiles, &skeleton[..1]).is_none());
+    }
+
+    #[test]
+    fn load_dir_skips_invalid() {
+        let dir = std::env::temp_dir().join("chaseconv_load_dir_skips_invalid");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.profile"), "bone_0 = Pelvis").unwrap();
+        fs::write(dir.join("b.profile"), "invalid").unwrap();
+        fs::write(dir.join("c.txt"), "invalid").unwrap();
+
+        let profiles = SkeletonProfile::load_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(2, profiles.len());
+        assert_eq!("a", profiles[0].as_ref().unwrap().name);
+        assert!(profiles[1].is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use glam::{Mat4, Quat, Vec3};
+
+use crate::{
+    profile::SkeletonProfile,
+    scene::{Animation, Joint, Keyframe},
+};
+
+/// Specifies how the joints of the skeleton an animation was made for are matched with the
+/// joints of another skeleton.
+#[derive(Debug, Clone, Copy)]
+pub enum JointMatching<'a> {
+    /// Joints with the same index are matched.
+    Index,
+    /// Joints with the same name in the profiles of both skeletons are matched.
+    Names {
+        source: &'a SkeletonProfile,
+        target: &'a SkeletonProfile,
+    },
+    /// Joints at the same place in the hierarchy are matched: the roots in order, and then the
+    /// children of matched joints in order.
+    Hierarchy,
+}
+
+/// Returns the source joint matched with each joint of the target skeleton, if any.
+pub fn match_joints(
+    source: &[Joint],
+    target: &[Joint],
+    matching: JointMatching,
+) -> Vec<Option<usize>> {
+    match matching {
+        JointMatching::Index => (0..target.len())
+            .map(|index| (index < source.len()).then_some(index))
+            .collect(),
+        JointMatching::Names {
+            source: source_profile,
+            target: target_profile,
+        } => (0..target.len())
+            .map(|index| {
+                let name = target_profile.joint_name(index)?;
+                source_profile
+                    .joint_index(name)
+                    .filter(|&index| index < source.len())
+            })
+            .collect(),
+        JointMatching::Hierarchy => {
+            let mut result = vec![None; target.len()];
+            let mut pairs: Vec<_> = roots(source).zip(roots(target)).collect();
+            while let Some((source_index, target_index)) = pairs.pop() {
+                result[target_index] = Some(source_index);
+                pairs.extend(
+                    source[source_index]
+                        .children
+                        .iter()
+                        .copied()
+                        .zip(target[target_index].children.iter().copied()),
+                );
+            }
+            result
+        }
+    }
+}
+
+/// Returns a copy of the animation, made for the source skeleton, that moves the target
+/// skeleton in the same way. `joint_map` gives the source joint matched with each target joint,
+/// as returned by [`match_joints`].
+///
+/// Matched joints follow the rotations of their source joints, whereas the remaining joints keep
+/// their rest pose relative to their parents. Differences between the rest poses are
+/// compensated by aligning each bone (from a joint to its first child) with the bone of its
+/// source joint, so that an arm that rests horizontally in one skeleton and diagonally in the
+/// other points in the same direction in every frame. Translations and scales of the joints are
+/// not transferred, and the root translations are scaled by the ratio between the heights of
+/// both skeletons.
+pub fn retarget(
+    animation: &Animation,
+    source: &[Joint],
+    target: &[Joint],
+    joint_map: &[Option<usize>],
+) -> Animation {
+    let source_pose = RestPose::new(source);
+    let target_pose = RestPose::new(target);
+
+    // The rotation that takes the world rotation of each source joint to the world rotation of
+    // the target joint.
+    let offsets: Vec<_> = (0..target.len())
+        .map(|index| {
+            let source_index = joint_map.get(index).copied().flatten()?;
+            let alignment = match (
+                source_pose.bone_direction(source, source_index),
+                target_pose.bone_direction(target, index),
+            ) {
+                (Some(source_direction), Some(target_direction)) => {
+                    Quat::from_rotation_arc(target_direction, source_direction)
+                }
+                _ => Quat::IDENTITY,
+            };
+            Some((
+                source_index,
+                source_pose.rotations[source_index].inverse()
+                    * alignment
+                    * target_pose.rotations[index],
+            ))
+        })
+        .collect();
+
+    let scale = match (source_pose.height(), target_pose.height()) {
+        (source_height, target_height) if source_height > f32::EPSILON => {
+            target_height / source_height
+        }
+        _ => 1.,
+    };
+
+    let source_order = hierarchy_order(source);
+    let target_order = hierarchy_order(target);
+    let frames = animation
+        .frames
+        .iter()
+        .map(|frame| {
+            let source_rotations = world_rotations(source, &source_order, |index, parent| {
+                let transform = frame.transforms.get(index).copied().unwrap_or_default();
+                parent * transform.to_scale_rotation_translation().1
+            });
+            let target_rotations = world_rotations(target, &target_order, |index, parent| {
+                match offsets[index] {
+                    Some((source_index, offset)) => source_rotations[source_index] * offset,
+                    None => parent * target[index].rotation,
+                }
+            });
+
+            Keyframe {
+                translation: frame.translation * scale,
+                root_motion: frame.root_motion * scale,
+                option: frame.option,
+                transforms: (0..target.len())
+                    .map(|index| {
+                        let parent_rotation = target[index]
+                            .parent
+                            .map_or(Quat::IDENTITY, |parent| target_rotations[parent]);
+                        Mat4::from_quat(
+                            (parent_rotation.inverse() * target_rotations[index]).normalize(),
+                        )
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Animation {
+        name: animation.name.clone(),
+        sampling_rate: animation.sampling_rate,
+        frames,
+    }
+}
+
+/// The bind pose of a skeleton in world space.
+struct RestPose {
+    rotations: Vec<Quat>,
+    positions: Vec<Vec3>,
+}
+
+impl RestPose {
+    fn new(skeleton: &[Joint]) -> Self {
+        let (rotations, positions) = (0..skeleton.len())
+            .map(|index| {
+                let mut joint = &skeleton[index];
+                let mut transform =
+                    Mat4::from_rotation_translation(joint.rotation, joint.translation.into());
+                while let Some(parent) = joint.parent {
+                    joint = &skeleton[parent];
+                    transform =
+                        Mat4::from_rotation_translation(joint.rotation, joint.translation.into())
+                            * transform;
+                }
+
+                let (_, rotation, translation) = transform.to_scale_rotation_translation();
+                (rotation, translation)
+            })
+            .unzip();
+
+        Self {
+            rotations,
+            positions,
+        }
+    }
+
+    /// Returns the direction from the joint to its first child, or `None` if the joint has no
+    /// children or they're at the same position.
+    fn bone_direction(&self, skeleton: &[Joint], index: usize) -> Option<Vec3> {
+        let child = *skeleton[index].children.first()?;
+        (self.positions[child] - self.positions[index]).try_normalize()
+    }
+
+    /// Returns the vertical distance between the lowest and the highest joint.
+    fn height(&self) -> f32 {
+        let heights = self.positions.iter().map(|position| position.y);
+        heights.clone().fold(f32::MIN, f32::max) - heights.fold(f32::MAX, f32::min)
+    }
+}
+
+/// Returns the world rotation of every joint, given a function that computes it from the index
+/// of the joint and the world rotation of its parent. `order` must list the parents before their
+/// children.
+fn world_rotations(
+    skeleton: &[Joint],
+    order: &[usize],
+    world_rotation: impl Fn(usize, Quat) -> Quat,
+) -> Vec<Quat> {
+    let mut rotations = vec![Quat::IDENTITY; skeleton.len()];
+    for &index in order {
+        let parent_rotation = skeleton[index]
+            .parent
+            .map_or(Quat::IDENTITY, |parent| rotations[parent]);
+        rotations[index] = world_rotation(index, parent_rotation);
+    }
+    rotations
+}
+
+/// Returns the indices of the joints in an order where parents come before their children.
+fn hierarchy_order(skeleton: &[Joint]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(skeleton.len());
+    let mut stack: Vec<_> = roots(skeleton).collect();
+    while let Some(index) = stack.pop() {
+        order.push(index);
+        stack.extend(skeleton[index].children.iter().rev());
+    }
+    order
+}
+
+fn roots(skeleton: &[Joint]) -> impl Iterator<Item = usize> + '_ {
+    skeleton
+        .iter()
+        .enumerate()
+        .filter(|(_, joint)| joint.parent.is_none())
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3A;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::scene::Scene;
+
+    /// A skeleton made of a hip with an arm, whose hand is at the given offset from the
+    /// shoulder.
+    fn skeleton(hand: Vec3A) -> Vec<Joint> {
+        vec![
+            Joint {
+                translation: Vec3A::new(0., 1., 0.),
+                children: vec![1],
+                ..Default::default()
+            },
+            Joint {
+                translation: Vec3A::new(0., 0.5, 0.),
+                parent: Some(0),
+                children: vec![2],
+                ..Default::default()
+            },
+            Joint {
+                translation: hand,
+                parent: Some(1),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn matching() {
+        let source = skeleton(Vec3A::X);
+        let mut target = skeleton(Vec3A::X);
+        target.swap(0, 2);
+        target[0].parent = Some(1);
+        target[0].children = Vec::new();
+        target[1].parent = Some(2);
+        target[1].children = vec![0];
+        target[2].parent = None;
+        target[2].children = vec![1];
+
+        assert_eq!(
+            vec![Some(0), Some(1), Some(2)],
+            match_joints(&source, &target, JointMatching::Index)
+        );
+        assert_eq!(
+            vec![Some(2), Some(1), Some(0)],
+            match_joints(&source, &target, JointMatching::Hierarchy)
+        );
+
+        let profile = |names: &str| SkeletonProfile::parse("test", names).unwrap();
+        let source_profile = profile("bone_0 = Hip\nbone_1 = Shoulder\nbone_2 = Hand");
+        let target_profile = profile("bone_0 = Hand\nbone_1 = Elbow\nbone_2 = Hip");
+        let names = JointMatching::Names {
+            source: &source_profile,
+            target: &target_profile,
+        };
+        assert_eq!(
+            vec![Some(2), None, Some(0)],
+            match_joints(&source, &target, names)
+        );
+    }
+
+    #[test]
+    fn rest_pose_compensation() {
+        // The source arm rests horizontally, while the target arm rests diagonally, which makes
+        // the target skeleton twice as tall.
+        let source = skeleton(Vec3A::X);
+        let target = skeleton(Vec3A::new(1., -1., 0.));
+        let animation = Animation {
+            name: String::from("salute"),
+            sampling_rate: 55.,
+            frames: vec![Keyframe {
+                translation: Vec3A::new(1., 0., 0.),
+                root_motion: Vec3A::ZERO,
+                option: 3,
+                transforms: vec![
+                    Mat4::IDENTITY,
+                    Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2),
+                    Mat4::IDENTITY,
+                ],
+            }],
+        };
+
+        let joint_map = match_joints(&source, &target, JointMatching::Index);
+        let actual = retarget(&animation, &source, &target, &joint_map);
+
+        assert_eq!(3, actual.frames[0].option);
+        assert_eq!(Vec3A::new(2., 0., 0.), actual.frames[0].translation);
+
+        // The arm points up in both skeletons.
+        let scene = Scene {
+            skeleton: target,
+            ..Default::default()
+        };
+        let frame = &actual.frames[0];
+        let shoulder = scene.joint_posed_transform(1, frame).w_axis.truncate();
+        let hand = scene.joint_posed_transform(2, frame).w_axis.truncate();
+        assert!((hand - shoulder).normalize().abs_diff_eq(Vec3::Y, 1e-5));
+    }
+
+    #[test]
+    fn same_skeleton() {
+        let source = skeleton(Vec3A::X);
+        let animation = Animation {
+            name: String::from("wave"),
+            sampling_rate: 55.,
+            frames: vec![Keyframe {
+                translation: Vec3A::ZERO,
+                root_motion: Vec3A::ZERO,
+                option: 0,
+                transforms: vec![
+                    Mat4::from_rotation_y(0.5),
+                    Mat4::from_rotation_x(1.),
+                    Mat4::from_rotation_z(-0.3),
+                ],
+            }],
+        };
+
+        let joint_map = match_joints(&source, &source, JointMatching::Index);
+        let actual = retarget(&animation, &source, &source, &joint_map);
+
+        for (expected, actual) in animation.frames[0]
+            .transforms
+            .iter()
+            .zip(&actual.frames[0].transforms)
+        {
+            assert!(expected.abs_diff_eq(*actual, 1e-5));
+        }
+    }
+}
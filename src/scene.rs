@@ -1,5 +1,13 @@
+use std::ops::RangeInclusive;
+
 use glam::{Mat4, Quat, Vec2, Vec3A};
 
+use crate::{
+    mirror,
+    profile::SkeletonProfile,
+    retarget::{self, JointMatching},
+};
+
 /// Represents a 3D scene comprised of skeleton, meshes, and animations.
 /// It's the intermediary format between conversions and provides some operations.
 ///
@@ -28,6 +36,81 @@ impl Scene {
         transform
     }
 
+    /// Returns the transform of the joint with the given index at the given keyframe, relative to
+    /// the origin of the scene. The translation of the keyframe is applied to the root joints.
+    pub fn joint_posed_transform(&self, index: usize, frame: &Keyframe) -> Mat4 {
+        let local_transform = |index: usize| {
+            let joint = &self.skeleton[index];
+            let transform = frame.transforms.get(index).copied().unwrap_or_default();
+            Mat4::from_translation(joint.translation.into()).mul_mat4(&transform)
+        };
+
+        let mut joint = &self.skeleton[index];
+        let mut transform = local_transform(index);
+        while let Some(parent) = joint.parent {
+            joint = &self.skeleton[parent];
+            transform = local_transform(parent).mul_mat4(&transform);
+        }
+
+        Mat4::from_translation(frame.translation.into()).mul_mat4(&transform)
+    }
+
+    /// Returns the skinning matrix of every joint at the given keyframe. A skinning matrix
+    /// transforms a vertex attached to the joint from the bind pose to the animated pose.
+    pub fn skinning_matrices(&self, frame: &Keyframe) -> Vec<Mat4> {
+        (0..self.skeleton.len())
+            .map(|index| {
+                self.joint_posed_transform(index, frame)
+                    .mul_mat4(&self.joint_world_transform(index).inverse())
+            })
+            .collect()
+    }
+
+    /// Removes the rotations of the joints in the bind pose while keeping their positions, since
+    /// the game formats cannot represent them. The animations are adjusted so that the posed
+    /// skeleton stays the same. Vertices are stored relative to the origin of the scene, so
+    /// meshes are not affected.
+    pub fn bake_bind_pose_rotations(&mut self) {
+        if self
+            .skeleton
+            .iter()
+            .all(|joint| joint.rotation == Quat::IDENTITY)
+        {
+            return;
+        }
+
+        let (world_rotations, world_positions): (Vec<_>, Vec<_>) = (0..self.skeleton.len())
+            .map(|index| {
+                let (_, rotation, translation) = self
+                    .joint_world_transform(index)
+                    .to_scale_rotation_translation();
+                (Mat4::from_quat(rotation), Vec3A::from(translation))
+            })
+            .unzip();
+
+        for animation in &mut self.animations {
+            for frame in &mut animation.frames {
+                for (index, transform) in frame.transforms.iter_mut().enumerate() {
+                    let Some(joint) = self.skeleton.get(index) else {
+                        break;
+                    };
+                    let parent_rotation = joint
+                        .parent
+                        .map_or(Mat4::IDENTITY, |parent| world_rotations[parent]);
+                    *transform = parent_rotation * *transform * world_rotations[index].inverse();
+                }
+            }
+        }
+
+        for (index, joint) in self.skeleton.iter_mut().enumerate() {
+            let parent_position = joint
+                .parent
+                .map_or(Vec3A::ZERO, |parent| world_positions[parent]);
+            joint.translation = world_positions[index] - parent_position;
+            joint.rotation = Quat::IDENTITY;
+        }
+    }
+
     pub fn merge(mut self, mut other: Scene) -> Self {
         if self.skeleton.is_empty() {
             self.skeleton = other.skeleton;
@@ -64,10 +147,13 @@ pub struct Joint {
     pub children: Vec<usize>,
 }
 
-/// Represents a keyframe animation sequence. It should be sampled at 55 FPS.
+/// Represents a keyframe animation sequence. The keyframes are evenly spaced in time according
+/// to the sampling rate of the animation.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Animation {
     pub name: String,
+    /// The number of keyframes per second.
+    pub sampling_rate: f32,
     pub frames: Vec<Keyframe>,
 }
 
@@ -85,8 +171,224 @@ impl Animation {
         result
     }
 
-    pub fn sampling_rate(&self) -> i32 {
-        55
+    /// Returns the time of the keyframe with the given index, in seconds.
+    pub fn time(&self, index: usize) -> f32 {
+        index as f32 / self.sampling_rate
+    }
+
+    /// Returns the duration of the animation, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.time(self.frames.len().saturating_sub(1))
+    }
+
+    /// Returns the keyframe at the given time, interpolating between the closest keyframes.
+    /// Times outside of the animation are clamped. Returns `None` if there are no keyframes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sampling rate of the animation isn't a positive finite number.
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        assert_valid_rate(self.sampling_rate, "sampling rate");
+        let position = (time * self.sampling_rate).max(0.);
+        let prev = (position.floor() as usize).min(self.frames.len().checked_sub(1)?);
+        let factor = position - prev as f32;
+
+        match self.frames.get(prev + 1) {
+            Some(next) if factor > 0. => Some(self.frames[prev].lerp(next, factor)),
+            _ => Some(self.frames[prev].clone()),
+        }
+    }
+
+    /// Returns a copy of the animation sampled at a different rate. The duration of the
+    /// animation is preserved, up to the precision of the new sampling rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either sampling rate isn't a positive finite number.
+    pub fn resample(&self, sampling_rate: f32) -> Animation {
+        assert_valid_rate(self.sampling_rate, "sampling rate");
+        assert_valid_rate(sampling_rate, "sampling rate");
+        if sampling_rate == self.sampling_rate {
+            return self.clone();
+        }
+
+        let num_frames = if !self.frames.is_empty() {
+            (self.duration() * sampling_rate).round() as usize + 1
+        } else {
+            0
+        };
+
+        Animation {
+            name: self.name.clone(),
+            sampling_rate,
+            frames: (0..num_frames)
+                .filter_map(|i| self.sample(i as f32 / sampling_rate))
+                .collect(),
+        }
+    }
+
+    /// Returns the keyframes in the given range, which is clamped to the animation. The root is
+    /// moved horizontally so that the animation starts where the original one started.
+    pub fn trim(&self, frames: RangeInclusive<usize>) -> Animation {
+        let end = (*frames.end()).min(self.frames.len().saturating_sub(1));
+        let mut trimmed = self
+            .frames
+            .get(*frames.start()..=end)
+            .unwrap_or_default()
+            .to_vec();
+        if let (Some(first), Some(start)) = (self.frames.first(), trimmed.first()) {
+            let offset = horizontal_offset(start, first);
+            offset_root(&mut trimmed, &offset);
+        }
+
+        Animation {
+            name: self.name.clone(),
+            sampling_rate: self.sampling_rate,
+            frames: trimmed,
+        }
+    }
+
+    /// Returns the animation followed by `other`, which is resampled to the same rate. The root
+    /// of `other` is moved horizontally so that it continues from the last keyframe, and
+    /// `blend_frames` keyframes that interpolate between both animations are inserted in between.
+    /// The root keeps moving through the blended keyframes at the speed of the last keyframe.
+    pub fn concatenate(&self, other: &Animation, blend_frames: usize) -> Animation {
+        let mut following = other.resample(self.sampling_rate).frames;
+        let mut frames = self.frames.clone();
+        if let (Some(last), Some(next)) = (self.frames.last(), following.first()) {
+            let (velocity, root_motion_velocity) = match self.frames.len() {
+                len if len >= 2 => horizontal_offset(&self.frames[len - 2], last),
+                _ => (Vec3A::ZERO, Vec3A::ZERO),
+            };
+            let steps = (blend_frames + 1) as f32;
+            let (translation, root_motion) = horizontal_offset(next, last);
+            let offset = (
+                translation + velocity * steps,
+                root_motion + root_motion_velocity * steps,
+            );
+            offset_root(&mut following, &offset);
+
+            let next = &following[0];
+            frames.extend(
+                (1..=blend_frames)
+                    .map(|index| last.lerp(next, index as f32 / (blend_frames + 1) as f32)),
+            );
+        }
+        frames.extend(following);
+
+        Animation {
+            name: self.name.clone(),
+            sampling_rate: self.sampling_rate,
+            frames,
+        }
+    }
+
+    /// Returns a copy of the animation played `speed` times faster (or slower, if it's less than
+    /// one), resampled at the same rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` or the sampling rate of the animation isn't a positive finite number.
+    pub fn change_speed(&self, speed: f32) -> Animation {
+        assert_valid_rate(speed, "speed");
+        let scaled = Animation {
+            sampling_rate: self.sampling_rate * speed,
+            ..self.clone()
+        };
+        scaled.resample(self.sampling_rate)
+    }
+
+    /// Returns a copy of the animation played backwards. The root is moved horizontally so that
+    /// the animation starts where the original one started.
+    pub fn reverse(&self) -> Animation {
+        let mut frames: Vec<_> = self.frames.iter().rev().cloned().collect();
+        if let (Some(first), Some(last)) = (self.frames.first(), self.frames.last()) {
+            offset_root(&mut frames, &horizontal_offset(last, first));
+        }
+
+        Animation {
+            name: self.name.clone(),
+            sampling_rate: self.sampling_rate,
+            frames,
+        }
+    }
+
+    /// Returns a copy of the animation that loops seamlessly: the joints of the last
+    /// `blend_frames` keyframes are gradually blended into the pose of the first keyframe, which
+    /// the last keyframe matches. The root translations and the options are kept, so that the
+    /// character still moves forward in walk cycles.
+    pub fn close_loop(&self, blend_frames: usize) -> Animation {
+        let mut frames = self.frames.clone();
+        let blend_frames = blend_frames.min(frames.len().saturating_sub(1));
+        if let Some(first) = self.frames.first() {
+            let start = frames.len() - blend_frames;
+            for (index, frame) in frames[start..].iter_mut().enumerate() {
+                let target = Keyframe {
+                    translation: Vec3A::new(
+                        frame.translation.x,
+                        first.translation.y,
+                        frame.translation.z,
+                    ),
+                    transforms: first.transforms.clone(),
+                    ..frame.clone()
+                };
+                *frame = frame.lerp(&target, (index + 1) as f32 / blend_frames as f32);
+            }
+        }
+
+        Animation {
+            name: self.name.clone(),
+            sampling_rate: self.sampling_rate,
+            frames,
+        }
+    }
+
+    /// Returns a copy of the animation mirrored across the sagittal plane of the character, with
+    /// the joints on the left and right sides swapped according to their names in the profile.
+    /// See [`mirror::mirror`] for details.
+    pub fn mirror(&self, profile: &SkeletonProfile) -> Animation {
+        let joint_map = mirror::mirror_joints(profile);
+        mirror::mirror(self, &joint_map, mirror::SAGITTAL_NORMAL)
+    }
+
+    /// Returns a copy of the animation, made for the `source` skeleton, that moves the `target`
+    /// skeleton in the same way. See [`retarget::retarget`] for details.
+    pub fn retarget(
+        &self,
+        source: &[Joint],
+        target: &[Joint],
+        matching: JointMatching,
+    ) -> Animation {
+        let joint_map = retarget::match_joints(source, target, matching);
+        retarget::retarget(self, source, target, &joint_map)
+    }
+}
+
+/// Panics unless a sampling rate or speed factor is a positive finite number, which would
+/// otherwise result in infinite or NaN frame counts.
+fn assert_valid_rate(rate: f32, name: &str) {
+    assert!(
+        rate > 0. && rate.is_finite(),
+        "The {} must be a positive finite number, but it's {}",
+        name,
+        rate
+    );
+}
+
+/// Returns the horizontal offsets of the translation and the root motion that move the `from`
+/// keyframe to the position of the `to` keyframe.
+fn horizontal_offset(from: &Keyframe, to: &Keyframe) -> (Vec3A, Vec3A) {
+    let horizontal = Vec3A::new(1., 0., 1.);
+    (
+        (to.translation - from.translation) * horizontal,
+        (to.root_motion - from.root_motion) * horizontal,
+    )
+}
+
+fn offset_root(frames: &mut [Keyframe], (translation, root_motion): &(Vec3A, Vec3A)) {
+    for frame in frames {
+        frame.translation += *translation;
+        frame.root_motion += *root_motion;
     }
 }
 
@@ -109,12 +411,52 @@ pub struct Vertex {
 pub struct Keyframe {
     /// The translation of applied to the whole skeleton.
     pub translation: Vec3A,
+    /// The motion of the character that is kept apart from the skeleton, which doesn't move it.
+    /// It's zero unless the motion was extracted with [`RootMotion::Separate`].
+    ///
+    /// [`RootMotion::Separate`]: crate::formats::RootMotion::Separate
+    pub root_motion: Vec3A,
+    /// The flags of the frame in FRM files, which the game client reacts to. It's zero for most
+    /// frames.
+    pub option: u8,
     /// The list of matrices for each joint at the current frame.
     /// Each matrix in the list should correspond to the joint with same
     /// index in the [`Scene`] skeleton.
     pub transforms: Vec<Mat4>,
 }
 
+impl Keyframe {
+    /// Interpolates between two keyframes. Joint rotations are spherically interpolated, the
+    /// option is taken from the nearest previous keyframe, and the remaining components are
+    /// linearly interpolated.
+    pub fn lerp(&self, other: &Keyframe, factor: f32) -> Keyframe {
+        Keyframe {
+            translation: self.translation.lerp(other.translation, factor),
+            root_motion: self.root_motion.lerp(other.root_motion, factor),
+            // Flags can't be interpolated, so they're kept until the next keyframe.
+            option: if factor < 1. {
+                self.option
+            } else {
+                other.option
+            },
+            transforms: self
+                .transforms
+                .iter()
+                .zip(&other.transforms)
+                .map(|(a, b)| {
+                    let (scale_a, rotation_a, translation_a) = a.to_scale_rotation_translation();
+                    let (scale_b, rotation_b, translation_b) = b.to_scale_rotation_translation();
+                    Mat4::from_scale_rotation_translation(
+                        scale_a.lerp(scale_b, factor),
+                        rotation_a.slerp(rotation_b, factor),
+                        translation_a.lerp(translation_b, factor),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use glam::Vec3;
@@ -171,4 +513,267 @@ mod tests {
         let expected = Mat4::from_translation(Vec3::new(3., 3., 3.));
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn animation_resample() {
+        let animation = Animation {
+            name: String::from("walk"),
+            sampling_rate: 2.,
+            frames: vec![
+                Keyframe {
+                    translation: Vec3A::new(0., 0., 0.),
+                    root_motion: Vec3A::ZERO,
+                    option: 0,
+                    transforms: vec![Mat4::IDENTITY],
+                },
+                Keyframe {
+                    translation: Vec3A::new(1., 0., 0.),
+                    root_motion: Vec3A::ZERO,
+                    option: 0,
+                    transforms: vec![Mat4::from_rotation_y(std::f32::consts::PI / 2.)],
+                },
+            ],
+        };
+
+        let actual = animation.resample(4.);
+        assert_eq!(4., actual.sampling_rate);
+        assert_eq!(3, actual.frames.len());
+        assert_eq!(animation.duration(), actual.duration());
+        assert_eq!(animation.frames[0], actual.frames[0]);
+        assert_eq!(animation.frames[1], actual.frames[2]);
+        assert_eq!(Vec3A::new(0.5, 0., 0.), actual.frames[1].translation);
+        assert!(actual.frames[1].transforms[0]
+            .abs_diff_eq(Mat4::from_rotation_y(std::f32::consts::PI / 4.), 1e-5));
+    }
+
+    /// Returns an animation that moves along X by the given positions, with a joint rotated by
+    /// a tenth of them around Y.
+    fn walk(positions: &[f32]) -> Animation {
+        Animation {
+            name: String::from("walk"),
+            sampling_rate: 55.,
+            frames: positions
+                .iter()
+                .enumerate()
+                .map(|(index, &x)| Keyframe {
+                    translation: Vec3A::new(x, 1., 0.),
+                    root_motion: Vec3A::ZERO,
+                    option: index as u8,
+                    transforms: vec![Mat4::from_rotation_y(x * 0.1)],
+                })
+                .collect(),
+        }
+    }
+
+    fn translations(animation: &Animation) -> Vec<f32> {
+        animation
+            .frames
+            .iter()
+            .map(|frame| frame.translation.x)
+            .collect()
+    }
+
+    #[test]
+    fn animation_trim() {
+        let animation = walk(&[0., 1., 3., 6.]);
+
+        let actual = animation.trim(1..=2);
+        assert_eq!(vec![0., 2.], translations(&actual));
+        assert_eq!(animation.frames[1].transforms, actual.frames[0].transforms);
+        assert_eq!(1, actual.frames[0].option);
+
+        assert_eq!(3, animation.trim(1..=10).frames.len());
+        assert!(animation.trim(5..=10).frames.is_empty());
+    }
+
+    #[test]
+    fn animation_concatenate() {
+        let actual = walk(&[0., 1.]).concatenate(&walk(&[5., 7.]), 1);
+
+        // The root keeps moving one unit per keyframe through the blend.
+        assert_eq!(vec![0., 1., 2., 3., 5.], translations(&actual));
+        let options: Vec<_> = actual.frames.iter().map(|frame| frame.option).collect();
+        assert_eq!(vec![0, 1, 1, 0, 1], options);
+        // The blend frame is halfway between the poses of both animations.
+        assert!(actual.frames[2].transforms[0].abs_diff_eq(Mat4::from_rotation_y(0.3), 1e-5));
+    }
+
+    #[test]
+    fn animation_change_speed() {
+        let animation = walk(&[0., 1., 2., 3., 4.]);
+
+        let actual = animation.change_speed(2.);
+        assert_eq!(55., actual.sampling_rate);
+        assert_eq!(3, actual.frames.len());
+        assert!((actual.frames[2].translation.x - 4.).abs() < 1e-5);
+
+        let actual = animation.change_speed(0.5);
+        assert_eq!(9, actual.frames.len());
+        assert!((actual.frames[1].translation.x - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    #[should_panic(expected = "The speed must be a positive finite number")]
+    fn animation_change_speed_zero() {
+        walk(&[0., 1.]).change_speed(0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "The sampling rate must be a positive finite number")]
+    fn animation_resample_nan() {
+        walk(&[0., 1.]).resample(f32::NAN);
+    }
+
+    #[test]
+    #[should_panic(expected = "The sampling rate must be a positive finite number")]
+    fn animation_sample_infinite_rate() {
+        let animation = Animation {
+            sampling_rate: f32::INFINITY,
+            ..walk(&[0., 1.])
+        };
+        animation.sample(0.5);
+    }
+
+    #[test]
+    fn animation_reverse() {
+        let animation = walk(&[0., 1., 3.]);
+        let actual = animation.reverse();
+
+        assert_eq!(vec![0., -2., -3.], translations(&actual));
+        assert_eq!(animation.frames[2].transforms, actual.frames[0].transforms);
+        assert_eq!(2, actual.frames[0].option);
+    }
+
+    #[test]
+    fn animation_close_loop() {
+        let animation = walk(&[0., 1., 2., 3., 4.]);
+
+        let actual = animation.close_loop(2);
+        assert_eq!(translations(&animation), translations(&actual));
+        assert_eq!(animation.frames[..3], actual.frames[..3]);
+        assert!(actual.frames[3].transforms[0].abs_diff_eq(Mat4::from_rotation_y(0.15), 1e-5));
+        assert!(actual.frames[4].transforms[0].abs_diff_eq(Mat4::IDENTITY, 1e-5));
+        assert_eq!(4, actual.frames[4].option);
+    }
+
+    #[test]
+    fn animation_sample_clamped() {
+        let animation = Animation {
+            name: String::new(),
+            sampling_rate: 55.,
+            frames: vec![
+                Keyframe {
+                    translation: Vec3A::new(1., 0., 0.),
+                    root_motion: Vec3A::ZERO,
+                    option: 0,
+                    transforms: Vec::new(),
+                },
+                Keyframe {
+                    translation: Vec3A::new(2., 0., 0.),
+                    root_motion: Vec3A::ZERO,
+                    option: 0,
+                    transforms: Vec::new(),
+                },
+            ],
+        };
+
+        assert_eq!(Some(animation.frames[0].clone()), animation.sample(-1.));
+        assert_eq!(Some(animation.frames[1].clone()), animation.sample(1.));
+    }
+
+    #[test]
+    fn bake_bind_pose_rotations() {
+        let rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        let pose = Quat::from_rotation_x(1.);
+        let mut scene = Scene {
+            meshes: Vec::new(),
+            skeleton: vec![
+                Joint {
+                    translation: Vec3A::new(0., 1., 0.),
+                    rotation,
+                    parent: None,
+                    children: vec![1],
+                },
+                Joint {
+                    translation: Vec3A::new(1., 0., 0.),
+                    rotation: Quat::IDENTITY,
+                    parent: Some(0),
+                    children: Vec::new(),
+                },
+            ],
+            animations: vec![Animation {
+                name: String::new(),
+                sampling_rate: 55.,
+                frames: vec![
+                    Keyframe {
+                        translation: Vec3A::ZERO,
+                        root_motion: Vec3A::ZERO,
+                        option: 0,
+                        transforms: vec![Mat4::from_quat(rotation), Mat4::IDENTITY],
+                    },
+                    Keyframe {
+                        translation: Vec3A::ZERO,
+                        root_motion: Vec3A::ZERO,
+                        option: 0,
+                        transforms: vec![Mat4::from_quat(rotation * pose), Mat4::IDENTITY],
+                    },
+                ],
+            }],
+        };
+
+        scene.bake_bind_pose_rotations();
+
+        assert_eq!(Quat::IDENTITY, scene.skeleton[0].rotation);
+        assert_eq!(Quat::IDENTITY, scene.skeleton[1].rotation);
+        assert!(scene.skeleton[1]
+            .translation
+            .abs_diff_eq(Vec3A::new(0., 1., 0.), 1e-6));
+
+        // The bind pose of the animation becomes the identity.
+        let frames = &scene.animations[0].frames;
+        assert!(frames[0].transforms[0].abs_diff_eq(Mat4::IDENTITY, 1e-6));
+        assert!(frames[0].transforms[1].abs_diff_eq(Mat4::IDENTITY, 1e-6));
+        // The pose rotation is expressed in the world-aligned frame of the joint.
+        let expected = Mat4::from_quat(rotation * pose * rotation.inverse());
+        assert!(frames[1].transforms[0].abs_diff_eq(expected, 1e-6));
+    }
+
+    #[test]
+    fn skinning_matrices() {
+        let scene = Scene {
+            meshes: Vec::new(),
+            skeleton: vec![
+                Joint {
+                    translation: Vec3A::new(0., 1., 0.),
+                    rotation: Quat::IDENTITY,
+                    parent: None,
+                    children: vec![1],
+                },
+                Joint {
+                    translation: Vec3A::new(1., 0., 0.),
+                    rotation: Quat::IDENTITY,
+                    parent: Some(0),
+                    children: Vec::new(),
+                },
+            ],
+            animations: Vec::new(),
+        };
+        let frame = Keyframe {
+            translation: Vec3A::new(0., 0., 2.),
+            root_motion: Vec3A::ZERO,
+            option: 0,
+            transforms: vec![
+                Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2),
+                Mat4::IDENTITY,
+            ],
+        };
+
+        let matrices = scene.skinning_matrices(&frame);
+        // The tip of the child joint rotates around the root joint.
+        let actual = matrices[1].transform_point3(Vec3::new(2., 1., 0.));
+        assert!(actual.abs_diff_eq(Vec3::new(0., 3., 2.), 1e-6));
+        // The root joint stays in place, apart from the keyframe translation.
+        let actual = matrices[0].transform_point3(Vec3::new(0., 1., 0.));
+        assert!(actual.abs_diff_eq(Vec3::new(0., 1., 2.), 1e-6));
+    }
 }
@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+
+use glam::{Mat4, Quat, Vec3, Vec3A};
+
+use crate::{
+    formats::p3m::internal::MAX_NUM_BONES,
+    scene::{Animation, Joint, Keyframe, Mesh, Scene, Vertex},
+};
+
+/// The joints that influence a vertex, along with their weights.
+pub type Influences = Vec<(usize, f32)>;
+
+/// The number of weight steps used to quantize helper joints. For instance, 4 steps produce
+/// helper joints that follow 25%, 50%, and 75% of the rotation of a joint.
+const HELPER_STEPS: f32 = 4.;
+/// The maximum number of children of a joint in the game formats.
+const MAX_NUM_CHILDREN: usize = 10;
+/// The rotation applied to every joint to estimate the error when there are no animations.
+const TEST_POSE_ANGLE: f32 = std::f32::consts::FRAC_PI_6;
+
+/// Specifies how multiple joint influences are reduced to the single influence per vertex
+/// supported by the game. By default, each vertex is attached to its most influential joint.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SkinningOptions {
+    /// Attach vertices influenced by both a joint and its parent to helper joints, which
+    /// follow a fraction of the rotation of the joint. The animations of the scene are
+    /// extended accordingly.
+    pub helper_joints: bool,
+    /// Split the triangles whose vertices are attached to different joints along the boundary
+    /// between the weights of the joints.
+    pub split_triangles: bool,
+}
+
+/// Summarizes how much the reduced skinning deviates from the original one.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SkinningReport {
+    /// The maximum distance between the position of each vertex with the original and with
+    /// the reduced skinning, over all the keyframes of the scene. Indexed by mesh and vertex.
+    pub errors: Vec<Vec<f32>>,
+    /// The number of helper joints added to the skeleton.
+    pub num_helper_joints: usize,
+    /// The number of triangles that were split.
+    pub num_split_triangles: usize,
+}
+
+impl SkinningReport {
+    /// Returns the mesh index, vertex index, and error of the vertices whose error exceeds the
+    /// given threshold, sorted from the largest error to the smallest.
+    pub fn worst_vertices(&self, threshold: f32) -> Vec<(usize, usize, f32)> {
+        let mut result: Vec<_> = self
+            .errors
+            .iter()
+            .enumerate()
+            .flat_map(|(mesh, errors)| {
+                errors
+                    .iter()
+                    .enumerate()
+                    .map(move |(vertex, &error)| (mesh, vertex, error))
+            })
+            .filter(|&(_, _, error)| error > threshold)
+            .collect();
+        result.sort_by(|a, b| b.2.total_cmp(&a.2));
+        result
+    }
+}
+
+/// Attaches every vertex of the scene meshes to a single joint, given the original influences
+/// of the vertices. The influences are indexed by mesh and vertex. The skeleton should have no
+/// rotations in the bind pose.
+///
+/// Helper joints only follow the animations that are already in the scene.
+pub fn reduce_influences(
+    scene: &mut Scene,
+    influences: &[Vec<Influences>],
+    options: SkinningOptions,
+) -> SkinningReport {
+    let mut reducer = Reducer {
+        skeleton: &mut scene.skeleton,
+        animations: &mut scene.animations,
+        helpers: HashMap::new(),
+        options,
+    };
+
+    let mut report = SkinningReport::default();
+    let mut all_influences = Vec::new();
+    for (mesh, influences) in scene.meshes.iter_mut().zip(influences) {
+        let mut influences: Vec<_> = influences.iter().map(normalize).collect();
+        influences.resize(mesh.vertices.len(), Vec::new());
+
+        for (vertex, influences) in mesh.vertices.iter_mut().zip(&influences) {
+            vertex.joint = reducer.assign(influences);
+        }
+        if options.split_triangles {
+            report.num_split_triangles += reducer.split_triangles(mesh, &mut influences);
+        }
+
+        all_influences.push(influences);
+    }
+    report.num_helper_joints = reducer.helpers.len();
+
+    report.errors = measure_errors(scene, &all_influences);
+    report
+}
+
+struct Reducer<'a> {
+    skeleton: &'a mut Vec<Joint>,
+    animations: &'a mut Vec<Animation>,
+    /// The helper joint of each (joint, weight step) pair.
+    helpers: HashMap<(usize, u32), usize>,
+    options: SkinningOptions,
+}
+
+impl Reducer<'_> {
+    /// Returns the single joint that best approximates the given normalized influences.
+    fn assign(&mut self, influences: &Influences) -> Option<usize> {
+        let &(first, first_weight) = influences.first()?;
+        let Some(&(second, second_weight)) = influences.get(1) else {
+            return Some(first);
+        };
+        if !self.options.helper_joints {
+            return Some(first);
+        }
+
+        // Helper joints are only used between a joint and its parent.
+        let (parent, child, child_weight) = if self.skeleton[second].parent == Some(first) {
+            (
+                first,
+                second,
+                second_weight / (first_weight + second_weight),
+            )
+        } else if self.skeleton[first].parent == Some(second) {
+            (second, first, first_weight / (first_weight + second_weight))
+        } else {
+            return Some(first);
+        };
+
+        match (child_weight * HELPER_STEPS).round() as u32 {
+            0 => Some(parent),
+            step if step as f32 >= HELPER_STEPS => Some(child),
+            step => Some(self.helper(child, step).unwrap_or(first)),
+        }
+    }
+
+    /// Returns the helper joint that follows the given fraction of the rotation of a joint,
+    /// creating it if necessary. Returns `None` if the skeleton cannot hold more joints.
+    fn helper(&mut self, joint: usize, step: u32) -> Option<usize> {
+        if let Some(&helper) = self.helpers.get(&(joint, step)) {
+            return Some(helper);
+        }
+
+        let parent = self.skeleton[joint].parent?;
+        if self.skeleton.len() >= MAX_NUM_BONES
+            || self.skeleton[parent].children.len() >= MAX_NUM_CHILDREN
+        {
+            return None;
+        }
+
+        let helper = self.skeleton.len();
+        self.skeleton.push(Joint {
+            translation: self.skeleton[joint].translation,
+            rotation: Quat::IDENTITY,
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.skeleton[parent].children.push(helper);
+
+        let weight = step as f32 / HELPER_STEPS;
+        for frame in self.animations.iter_mut().flat_map(|x| x.frames.iter_mut()) {
+            let transform = frame.transforms.get(joint).copied().unwrap_or_default();
+            frame.transforms.resize(helper, Mat4::IDENTITY);
+            frame.transforms.push(partial_transform(&transform, weight));
+        }
+
+        self.helpers.insert((joint, step), helper);
+        Some(helper)
+    }
+
+    /// Splits the edges whose vertices are attached to different joints, inserting a vertex
+    /// where the weights of both joints are equal. Returns the number of split triangles.
+    fn split_triangles(&mut self, mesh: &mut Mesh, influences: &mut Vec<Influences>) -> usize {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut indices = Vec::new();
+        let mut count = 0;
+
+        for face in mesh.indices.chunks_exact(3) {
+            let mut splits = [None; 3];
+            for edge in 0..3 {
+                let (a, b) = (face[edge], face[(edge + 1) % 3]);
+                if mesh.vertices[a].joint == mesh.vertices[b].joint {
+                    continue;
+                }
+
+                let key = (a.min(b), a.max(b));
+                let midpoint = match midpoints.get(&key) {
+                    Some(&midpoint) => midpoint,
+                    None => {
+                        let (vertex, vertex_influences) =
+                            self.split_edge(mesh, influences, key.0, key.1);
+                        mesh.vertices.push(vertex);
+                        influences.push(vertex_influences);
+                        midpoints.insert(key, mesh.vertices.len() - 1);
+                        mesh.vertices.len() - 1
+                    }
+                };
+                splits[edge] = Some(midpoint);
+            }
+
+            if splits.iter().all(Option::is_none) {
+                indices.extend_from_slice(face);
+            } else {
+                indices.extend(split_face([face[0], face[1], face[2]], splits));
+                count += 1;
+            }
+        }
+
+        mesh.indices = indices;
+        count
+    }
+
+    /// Returns the vertex of the edge between two vertices where the weights of their joints
+    /// are equal, along with its influences.
+    fn split_edge(
+        &mut self,
+        mesh: &Mesh,
+        influences: &[Influences],
+        a: usize,
+        b: usize,
+    ) -> (Vertex, Influences) {
+        let (vertex_a, vertex_b) = (&mesh.vertices[a], &mesh.vertices[b]);
+        let difference = |influences: &Influences| {
+            weight(influences, vertex_a.joint) - weight(influences, vertex_b.joint)
+        };
+        let (difference_a, difference_b) = (difference(&influences[a]), difference(&influences[b]));
+
+        let factor = if difference_a - difference_b > f32::EPSILON {
+            (difference_a / (difference_a - difference_b)).clamp(0., 1.)
+        } else {
+            0.5
+        };
+
+        let mut vertex_influences: Influences = Vec::new();
+        for (&(joint, weight), scale) in influences[a]
+            .iter()
+            .zip(std::iter::repeat(1. - factor))
+            .chain(influences[b].iter().zip(std::iter::repeat(factor)))
+        {
+            match vertex_influences.iter_mut().find(|(j, _)| *j == joint) {
+                Some((_, w)) => *w += weight * scale,
+                None => vertex_influences.push((joint, weight * scale)),
+            }
+        }
+        let vertex_influences = normalize(&vertex_influences);
+
+        let vertex = Vertex {
+            position: vertex_a.position.lerp(vertex_b.position, factor),
+            normal: vertex_a
+                .normal
+                .lerp(vertex_b.normal, factor)
+                .normalize_or_zero(),
+            uv: vertex_a.uv.lerp(vertex_b.uv, factor),
+            joint: self.assign(&vertex_influences),
+        };
+
+        (vertex, vertex_influences)
+    }
+}
+
+/// Returns the triangles that result from splitting the edges of a face. Each split contains
+/// the vertex inserted in the edge that starts at the vertex with the same index.
+fn split_face(face: [usize; 3], splits: [Option<usize>; 3]) -> Vec<usize> {
+    // Rotate the face so that the first edge is always split and, if there are two splits,
+    // the unsplit edge is the last one.
+    let rotation = match splits {
+        [Some(_), Some(_), None] | [Some(_), None, None] => 0,
+        [None, Some(_), Some(_)] | [None, Some(_), None] => 1,
+        [Some(_), None, Some(_)] | [None, None, Some(_)] => 2,
+        _ => 0,
+    };
+    let [v0, v1, v2] = [0, 1, 2].map(|i| face[(i + rotation) % 3]);
+    let [m01, m12, m20] = [0, 1, 2].map(|i| splits[(i + rotation) % 3]);
+
+    match (m01, m12, m20) {
+        (Some(m01), None, None) => vec![v0, m01, v2, m01, v1, v2],
+        (Some(m01), Some(m12), None) => vec![m01, v1, m12, v0, m01, m12, v0, m12, v2],
+        (Some(m01), Some(m12), Some(m20)) => {
+            vec![v0, m01, m20, v1, m12, m01, v2, m20, m12, m01, m12, m20]
+        }
+        _ => face.to_vec(),
+    }
+}
+
+/// Returns the given influences sorted by decreasing weight, without zero weights, and with
+/// weights that add up to one.
+fn normalize(influences: &Influences) -> Influences {
+    let mut result: Influences = influences
+        .iter()
+        .copied()
+        .filter(|&(_, weight)| weight > 0.)
+        .collect();
+    let total: f32 = result.iter().map(|(_, weight)| weight).sum();
+    for (_, weight) in &mut result {
+        *weight /= total;
+    }
+    result.sort_by(|a, b| b.1.total_cmp(&a.1));
+    result
+}
+
+/// Returns the weight of the given joint in the influences.
+fn weight(influences: &Influences, joint: Option<usize>) -> f32 {
+    influences
+        .iter()
+        .find(|&&(j, _)| Some(j) == joint)
+        .map_or(0., |&(_, weight)| weight)
+}
+
+/// Returns a transform that applies the given fraction of another transform.
+fn partial_transform(transform: &Mat4, weight: f32) -> Mat4 {
+    let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+    Mat4::from_scale_rotation_translation(
+        Vec3::ONE.lerp(scale, weight),
+        Quat::IDENTITY.slerp(rotation, weight),
+        Vec3::ZERO.lerp(translation, weight),
+    )
+}
+
+/// Returns the maximum distance between each vertex skinned with its original influences and
+/// with its single joint, over all the keyframes of the scene. If the scene has no animations,
+/// a few test poses are used instead.
+fn measure_errors(scene: &Scene, influences: &[Vec<Influences>]) -> Vec<Vec<f32>> {
+    let mut frames: Vec<&Keyframe> = scene
+        .animations
+        .iter()
+        .flat_map(|animation| &animation.frames)
+        .collect();
+    let test_poses: Vec<_> = [Vec3::X, Vec3::Y, Vec3::Z]
+        .into_iter()
+        .map(|axis| Keyframe {
+            translation: Vec3A::ZERO,
+            root_motion: Vec3A::ZERO,
+            option: 0,
+            transforms: vec![Mat4::from_axis_angle(axis, TEST_POSE_ANGLE); scene.skeleton.len()],
+        })
+        .collect();
+    if frames.is_empty() {
+        frames = test_poses.iter().collect();
+    }
+
+    let mut errors: Vec<Vec<f32>> = scene
+        .meshes
+        .iter()
+        .map(|mesh| vec![0.; mesh.vertices.len()])
+        .collect();
+    for frame in frames {
+        let matrices = scene.skinning_matrices(frame);
+        let skin = |joint: usize, position: Vec3A| match matrices.get(joint) {
+            Some(matrix) => matrix.transform_point3a(position),
+            None => position,
+        };
+
+        for ((mesh, influences), errors) in scene.meshes.iter().zip(influences).zip(&mut errors) {
+            for ((vertex, influences), error) in
+                mesh.vertices.iter().zip(influences).zip(errors.iter_mut())
+            {
+                if influences.is_empty() {
+                    continue;
+                }
+
+                let expected: Vec3A = influences
+                    .iter()
+                    .map(|&(joint, weight)| skin(joint, vertex.position) * weight)
+                    .sum();
+                let actual = match vertex.joint {
+                    Some(joint) => skin(joint, vertex.position),
+                    None => vertex.position,
+                };
+                *error = error.max(expected.distance(actual));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Returns an arm made of two joints, bent 90 degrees at the elbow, and a mesh with a vertex
+    /// next to the elbow influenced equally by both joints.
+    fn arm() -> (Scene, Vec<Vec<Influences>>) {
+        let vertex = |x: f32| Vertex {
+            position: Vec3A::new(x, 0., 0.),
+            normal: Vec3A::Y,
+            uv: Default::default(),
+            joint: None,
+        };
+        let scene = Scene {
+            meshes: vec![Mesh {
+                name: String::from("arm"),
+                vertices: vec![vertex(0.), vertex(1.5), vertex(2.)],
+                indices: vec![0, 1, 2],
+            }],
+            skeleton: vec![
+                Joint {
+                    children: vec![1],
+                    ..Default::default()
+                },
+                Joint {
+                    translation: Vec3A::new(1., 0., 0.),
+                    parent: Some(0),
+                    ..Default::default()
+                },
+            ],
+            animations: vec![Animation {
+                name: String::from("bend"),
+                sampling_rate: 55.,
+                frames: vec![Keyframe {
+                    translation: Vec3A::ZERO,
+                    root_motion: Vec3A::ZERO,
+                    option: 0,
+                    transforms: vec![
+                        Mat4::IDENTITY,
+                        Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2),
+                    ],
+                }],
+            }],
+        };
+        let influences = vec![vec![vec![(0, 1.)], vec![(0, 0.5), (1, 0.5)], vec![(1, 1.)]]];
+
+        (scene, influences)
+    }
+
+    #[test]
+    fn max_weight() {
+        let (mut scene, influences) = arm();
+
+        let report = reduce_influences(&mut scene, &influences, SkinningOptions::default());
+
+        let joints: Vec<_> = scene.meshes[0].vertices.iter().map(|v| v.joint).collect();
+        assert_eq!(vec![Some(0), Some(0), Some(1)], joints);
+        assert_eq!(0, report.num_helper_joints);
+        assert_eq!(0., report.errors[0][0]);
+        assert_eq!(0., report.errors[0][2]);
+        // The elbow vertex stays in place instead of moving halfway.
+        assert!(report.errors[0][1] > 0.);
+        assert_eq!(vec![(0, 1, report.errors[0][1])], report.worst_vertices(0.));
+    }
+
+    #[test]
+    fn helper_joints() {
+        let (mut scene, influences) = arm();
+        let options = SkinningOptions {
+            helper_joints: true,
+            split_triangles: false,
+        };
+
+        let report = reduce_influences(&mut scene, &influences, options);
+
+        assert_eq!(1, report.num_helper_joints);
+        assert_eq!(3, scene.skeleton.len());
+        assert_eq!(Some(0), scene.skeleton[2].parent);
+        assert_eq!(vec![1, 2], scene.skeleton[0].children);
+        assert_eq!(Some(2), scene.meshes[0].vertices[1].joint);
+
+        let expected = Mat4::from_rotation_z(std::f32::consts::FRAC_PI_4);
+        let actual = scene.animations[0].frames[0].transforms[2];
+        assert!(actual.abs_diff_eq(expected, 1e-6));
+
+        let (mut scene, influences) = arm();
+        let baseline = reduce_influences(&mut scene, &influences, SkinningOptions::default());
+        assert!(report.errors[0][1] < baseline.errors[0][1]);
+    }
+
+    #[test]
+    fn split_triangles() {
+        let (mut scene, _) = arm();
+        let influences = vec![vec![vec![(0, 1.)], vec![(0, 1.)], vec![(1, 1.)]]];
+        let options = SkinningOptions {
+            helper_joints: false,
+            split_triangles: true,
+        };
+
+        let report = reduce_influences(&mut scene, &influences, options);
+
+        assert_eq!(1, report.num_split_triangles);
+        let mesh = &scene.meshes[0];
+        // Both edges that reach the last vertex are split in half.
+        assert_eq!(5, mesh.vertices.len());
+        assert_eq!(Vec3A::new(1.75, 0., 0.), mesh.vertices[3].position);
+        assert_eq!(Vec3A::new(1., 0., 0.), mesh.vertices[4].position);
+        assert_eq!(vec![3, 2, 4, 1, 3, 4, 1, 4, 0], mesh.indices);
+    }
+
+    #[test]
+    fn split_face_patterns() {
+        assert_eq!(vec![0, 1, 2], split_face([0, 1, 2], [None; 3]));
+        assert_eq!(
+            vec![2, 3, 1, 3, 0, 1],
+            split_face([0, 1, 2], [None, None, Some(3)])
+        );
+        assert_eq!(
+            vec![0, 3, 5, 1, 4, 3, 2, 5, 4, 3, 4, 5],
+            split_face([0, 1, 2], [Some(3), Some(4), Some(5)])
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! Tests for glTF files whose buffers or meshes are compressed with an extension.
+
+use anyhow::Result;
+use chaseconv::{asset::Asset, conversion::Importer, formats::GltfImporter, scene::Scene};
+use glam::Vec3A;
+
+fn import(name: &str) -> Result<Scene> {
+    let path = format!(
+        "{}/tests/compressed/{}.gltf",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let asset = Asset::from_path(&path)?;
+
+    let mut scene = Scene::default();
+    GltfImporter::default().import(&asset, &mut scene)?;
+    Ok(scene)
+}
+
+#[test]
+fn meshopt() {
+    let scene = import("meshopt").unwrap();
+
+    assert_eq!(1, scene.meshes.len());
+    let mesh = &scene.meshes[0];
+    let positions: Vec<_> = mesh.vertices.iter().map(|vertex| vertex.position).collect();
+    assert_eq!(vec![Vec3A::ZERO, Vec3A::X, Vec3A::Y], positions);
+    assert_eq!(3, mesh.indices.len());
+}
+
+/// Draco isn't decoded, so files that require it are rejected.
+#[test]
+fn draco() {
+    let err = import("draco").unwrap_err();
+    assert!(format!("{:#}", err).contains("Draco-compressed meshes are not supported"));
+}
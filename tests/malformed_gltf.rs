@@ -0,0 +1,110 @@
+//! Regression tests for malformed glTF files. Each file must either be rejected with an error or
+//! be imported while skipping the invalid data, but it must never crash the importer.
+
+use anyhow::Result;
+use chaseconv::{asset::Asset, conversion::Importer, formats::GltfImporter, scene::Scene};
+
+fn import(name: &str) -> Result<Scene> {
+    let path = format!(
+        "{}/tests/malformed/{}.gltf",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let asset = Asset::from_path(&path)?;
+
+    let mut scene = Scene::default();
+    GltfImporter::default().import(&asset, &mut scene)?;
+    Ok(scene)
+}
+
+fn import_error(name: &str) -> String {
+    match import(name) {
+        Ok(_) => panic!("\"{}\" should not be imported", name),
+        Err(err) => format!("{:#}", err),
+    }
+}
+
+#[test]
+fn invalid_json() {
+    assert!(import("invalid_json").is_err());
+}
+
+#[test]
+fn missing_buffer_file() {
+    assert!(import_error("missing_buffer_file").contains("missing.bin"));
+}
+
+#[test]
+fn short_buffer() {
+    assert!(import_error("short_buffer").contains("Buffer 0 has 36 bytes"));
+}
+
+#[test]
+fn index_out_of_range() {
+    assert!(import_error("index_out_of_range").contains("references vertex 5"));
+}
+
+#[test]
+fn node_cycle() {
+    assert!(import("node_cycle").is_err());
+}
+
+#[test]
+fn shared_child() {
+    assert!(import_error("shared_child").contains("child of more than one node"));
+}
+
+#[test]
+fn empty_accessor() {
+    assert!(import_error("empty_accessor").contains("is empty"));
+}
+
+#[test]
+fn float_joints() {
+    assert!(import_error("float_joints").contains("unsupported type"));
+}
+
+#[test]
+fn huge_joint_index() {
+    assert!(import_error("huge_joint_index").contains("maximum number of joints"));
+}
+
+#[test]
+fn unsupported_extension() {
+    assert!(import_error("unsupported_extension").contains("\"EXT_unknown_compression\""));
+}
+
+#[test]
+fn line_primitive() {
+    let scene = import("line_primitive").unwrap();
+    assert!(scene.meshes.is_empty());
+}
+
+#[test]
+fn invalid_keyframes() {
+    for name in [
+        "infinite_keyframe_time",
+        "unsorted_keyframe_times",
+        "mismatched_keyframe_values",
+    ] {
+        let scene = import(name).unwrap();
+        assert_eq!(1, scene.animations.len(), "{}", name);
+        assert!(scene.animations[0].frames.is_empty(), "{}", name);
+    }
+}
+
+#[test]
+fn huge_animation_duration() {
+    let scene = import("huge_animation_duration").unwrap();
+    assert!(scene.animations.is_empty());
+}
+
+#[test]
+fn meshopt_huge_offset() {
+    assert!(import_error("meshopt_huge_offset").contains("is out of bounds"));
+}
+
+#[test]
+fn meshopt_huge_count() {
+    assert!(import_error("meshopt_huge_count").contains("don't match the length"));
+}